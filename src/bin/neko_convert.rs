@@ -0,0 +1,36 @@
+extern crate nlp100_rust;
+
+use nlp100_rust::chapter04::answer::{convert_json_to_store, convert_store_to_json};
+use nlp100_rust::chapter04::token_store::{TokenStore, TokenStoreError};
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::process;
+
+const USAGE: &str = "usage: neko_convert <input> <output>\n\
+                     converts JSON lines into a token store, or a token store back into JSON lines";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() != 2 {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+    let bytes = fs::read(&args[0]).expect(format!("file not found. {}", args[0]).as_str());
+    let mut out = BufWriter::new(
+        File::create(&args[1]).expect(format!("can't create file. {}", args[1]).as_str()),
+    );
+    // 先頭のマジックナンバーで変換の向きを決める。壊れたバイナリは JSON として読まない
+    let result = match TokenStore::open(&bytes) {
+        Ok(store) => convert_store_to_json(&store, &mut out),
+        Err(TokenStoreError::InvalidMagic) => {
+            convert_json_to_store(BufReader::new(bytes.as_slice()), &mut out)
+        }
+        Err(error) => Err(error),
+    };
+    if let Err(error) = result {
+        eprintln!("{}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,67 @@
+extern crate nlp100_rust;
+
+use nlp100_rust::chapter04::command::load_json;
+use nlp100_rust::chapter04::kwic::{query_pattern, Concordance, SortKey};
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::process;
+
+const USAGE: &str = "usage: neko_kwic [--window <n>] [--sort none|left|right] [--tsv] <input> <query>\n\
+                     query is a surface form, or a token pattern such as \"[pos=名詞] [surface=の]\"";
+
+fn main() {
+    let mut window = 5;
+    let mut sort = SortKey::default();
+    let mut tsv = false;
+    let mut positional = vec![];
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--window" => {
+                window = args
+                    .next()
+                    .unwrap_or_default()
+                    .parse::<usize>()
+                    .unwrap_or_else(|_| exit_with("--window requires a number"));
+            }
+            "--sort" => {
+                sort = args
+                    .next()
+                    .unwrap_or_default()
+                    .parse::<SortKey>()
+                    .unwrap_or_else(|e| exit_with(e.as_str()));
+            }
+            "--tsv" => tsv = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if positional.len() != 2 {
+        exit_with(USAGE);
+    }
+    let pattern =
+        query_pattern(positional[1].as_str()).unwrap_or_else(|e| exit_with(&e.to_string()));
+    let input = File::open(&positional[0])
+        .unwrap_or_else(|_| exit_with(&format!("file not found. {}", positional[0])));
+    let mut concordance = Concordance::new(pattern).with_window(window);
+    load_json(BufReader::new(input), &mut concordance);
+    concordance.sort(sort);
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    let result = if tsv {
+        concordance.write_tsv(&mut out)
+    } else {
+        concordance.write_aligned(&mut out)
+    };
+    result.expect("Error during writeln");
+}
+
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
@@ -0,0 +1,57 @@
+extern crate nlp100_rust;
+extern crate serde_json;
+
+use nlp100_rust::chapter04::answer::{NekoParser, TokenizerMode};
+use std::env;
+use std::process;
+use std::thread;
+
+const USAGE: &str = "usage: neko_parser [--mode normal|decompose] [--user-dict <csv>] \
+                     [--workers <n>] <input> <output>";
+
+fn main() {
+    let mut parser = NekoParser::default();
+    let mut paths = vec![];
+    let mut workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--mode" => {
+                let mode = args
+                    .next()
+                    .unwrap_or_default()
+                    .parse::<TokenizerMode>()
+                    .unwrap_or_else(|e| exit_with(e.as_str()));
+                parser = parser.with_mode(mode);
+            }
+            "--user-dict" => match args.next() {
+                Some(path) => parser = parser.with_user_dictionary(path.as_str()),
+                None => exit_with("--user-dict requires a path"),
+            },
+            "--workers" => {
+                workers = args
+                    .next()
+                    .unwrap_or_default()
+                    .parse::<usize>()
+                    .unwrap_or_else(|_| exit_with("--workers requires a number"));
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() != 2 {
+        exit_with(USAGE);
+    }
+    parser.parse_file_parallel(paths[0].as_str(), paths[1].as_str(), workers);
+    println!("{}", serde_json::to_string(&parser).unwrap());
+}
+
+fn exit_with(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
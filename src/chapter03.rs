@@ -1 +1,8 @@
 pub mod answer;
+pub mod category_graph;
+pub mod link_graph;
+pub mod markup;
+pub mod media;
+pub mod mediawiki;
+pub mod plain_text;
+pub mod table;
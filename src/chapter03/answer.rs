@@ -1,8 +1,8 @@
+use crate::chapter03::mediawiki::{MediaWikiClient, WIKIPEDIA_EN_API_URL};
+use async_trait::async_trait;
 use flate2::read::GzDecoder;
 use regex::Regex;
-use reqwest::StatusCode;
 use serde::Deserialize;
-use serde_json::Value;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -14,6 +14,21 @@ pub struct Article {
 }
 
 impl Article {
+    pub fn new(title: &str, text: &str) -> Article {
+        Article {
+            title: title.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        self.title.as_str()
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.as_str()
+    }
+
     pub fn lines_from_text(&self) -> Vec<String> {
         self.text.split("\n").map(|line| line.to_string()).collect()
     }
@@ -31,15 +46,18 @@ pub fn extract_ndjson_from_gzip(input_file_name: &str) -> Vec<String> {
 // ch03-20. JSONデータの読み込み
 // https://serde.rs/
 pub fn load_json(input_file_name: &str, target_title: &str) -> Vec<Article> {
-    let mut results = vec![];
+    return load_articles(input_file_name)
+        .into_iter()
+        .filter(|article| article.title == target_title)
+        .collect();
+}
+
+pub fn load_articles(input_file_name: &str) -> Vec<Article> {
     let ndjson = extract_ndjson_from_gzip(input_file_name);
-    for json in ndjson {
-        let article: Article = serde_json::from_str(json.as_str()).expect("json parse error");
-        if article.title == target_title {
-            results.push(article);
-        }
-    }
-    return results;
+    return ndjson
+        .iter()
+        .map(|json| serde_json::from_str(json.as_str()).expect("json parse error"))
+        .collect();
 }
 
 // ch03-21. カテゴリ名を含む行を抽出
@@ -71,6 +89,17 @@ pub struct Section {
     section: String,
     level: u8,
 }
+
+impl Section {
+    pub fn section(&self) -> &str {
+        self.section.as_str()
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+}
+
 pub fn extract_sections(article: &Article) -> Vec<Section> {
     let re = Regex::new(r"(={2,})([^=]+)(={2,})").expect("syntax error in regex");
     let mut sections: Vec<Section> = vec![];
@@ -127,7 +156,7 @@ pub trait Cleaner {
     fn remove_markup(&self, original: &str) -> String;
 }
 
-struct NoneCleaner {}
+pub struct NoneCleaner {}
 
 impl Cleaner for NoneCleaner {
     fn remove_markup(&self, original: &str) -> String {
@@ -136,7 +165,7 @@ impl Cleaner for NoneCleaner {
 }
 
 // ch03-26. 強調マークアップの除去
-struct StrongCleaner {}
+pub struct StrongCleaner {}
 
 impl Cleaner for StrongCleaner {
     fn remove_markup(&self, original: &str) -> String {
@@ -146,8 +175,8 @@ impl Cleaner for StrongCleaner {
 }
 
 // ch03-27. 内部リンクの除去
-struct LinkCleaner {
-    chain: StrongCleaner,
+pub struct LinkCleaner {
+    pub chain: StrongCleaner,
 }
 
 impl Cleaner for LinkCleaner {
@@ -168,92 +197,128 @@ impl Cleaner for LinkCleaner {
 //TODO...
 
 // ch03-29. 国旗画像のURLを取得する
-pub fn get_country_flag_url(basic_info: HashMap<String, String>) -> Option<String> {
-    return match basic_info.get("国旗画像") {
-        Some(file_name) => Some(get_image_url(file_name)),
-        None => None,
-    };
+// ランタイムは呼び出し側で用意する。国旗画像が無いときと URL が分からないときは None
+pub async fn get_country_flag_url<T: ImageUrlResolver>(
+    basic_info: HashMap<String, String>,
+    resolver: &T,
+) -> Option<String> {
+    let file_name = basic_info.get("国旗画像")?;
+    return get_image_url(file_name, resolver).await;
+}
+
+async fn get_image_url<T: ImageUrlResolver>(file_name: &str, resolver: &T) -> Option<String> {
+    return resolver.resolve(file_name).await.ok();
+}
+
+#[async_trait]
+pub trait ImageUrlResolver {
+    async fn resolve(&self, file_name: &str) -> Result<String, String>;
 }
 
-fn get_image_url(file_name: &str) -> String {
-    let mut _rt = tokio::runtime::Runtime::new().expect("Fail initializing runtime");
-    let task = call_api(file_name);
-    _rt.block_on(task).expect("Something wrong...")
+const WIKIMEDIA_UPLOAD_URL: &str = "https://upload.wikimedia.org/wikipedia/en";
+
+// MediaWiki APIのimageinfoでURLを問い合わせる
+pub struct ApiImageUrlResolver {
+    client: MediaWikiClient,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct MediaWikiResponse {
-    query: Value,
+impl ApiImageUrlResolver {
+    pub fn new(api_url: &str) -> ApiImageUrlResolver {
+        ApiImageUrlResolver {
+            client: MediaWikiClient::new(api_url),
+        }
+    }
 }
 
-impl MediaWikiResponse {
-    pub fn get_url(&self) -> Option<String> {
-        let pages = self.query.as_object()?.get("pages")?;
-        let key = pages.as_object().unwrap().keys().next()?;
-        return Some(String::from(
-            pages
-                .get(key)?
-                .as_object()?
-                .get("imageinfo")?
-                .as_array()?
-                .first()?
-                .as_object()
-                .unwrap()
-                .get("url")?
-                .as_str()?,
-        ));
+impl Default for ApiImageUrlResolver {
+    fn default() -> Self {
+        ApiImageUrlResolver::new(WIKIPEDIA_EN_API_URL)
     }
 }
 
-async fn call_api(file_name: &str) -> Result<String, String> {
-    let client = reqwest::Client::new();
-    let file_name2 = format!("File:{}", file_name);
-    //let mut file_name2 = file_name.to_string().replace(" ", "_");
-    let query = [
-        ("action", "query"),
-        ("format", "json"),
-        ("prop", "imageinfo"),
-        ("iiprop", "url"),
-        ("titles", file_name2.as_str()),
-    ];
-    let result = client
-        .get("https://en.wikipedia.org/w/api.php")
-        .query(&query)
-        .send()
-        .await;
-
-    match result {
-        Ok(response) => match response.status() {
-            StatusCode::OK => {
-                let body = response.json::<MediaWikiResponse>().await;
-                match body {
-                    Ok(obj) => match obj.get_url() {
-                        Some(url) => Ok(url),
-                        None => Err(String::from("Cannot get url...")),
-                    },
-                    Err(error) => Err(error.to_string()),
-                }
-            }
-            _ => Err(String::from(format!(
-                "Status code is {}.",
-                response.status()
-            ))),
-        },
-        Err(error) => {
-            let error_msg = format!("Error occurred... {:?}", error);
-            println!("{}", error_msg.as_str());
-            Err(error_msg)
+#[async_trait]
+impl ImageUrlResolver for ApiImageUrlResolver {
+    async fn resolve(&self, file_name: &str) -> Result<String, String> {
+        self.client
+            .image_url(file_name)
+            .await
+            .map_err(|error| error.to_string())
+    }
+}
+
+// ネットワークを使わず、ファイル名のMD5からupload.wikimedia.orgのパスを組み立てる
+// https://www.mediawiki.org/wiki/Manual:$wgHashedUploadDirectory
+pub struct HashedPathImageUrlResolver {
+    upload_url: String,
+}
+
+impl HashedPathImageUrlResolver {
+    pub fn new(upload_url: &str) -> HashedPathImageUrlResolver {
+        HashedPathImageUrlResolver {
+            upload_url: upload_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    pub fn image_url(&self, file_name: &str) -> String {
+        let normalized = normalize_file_name(file_name);
+        let hash = format!("{:x}", md5::compute(normalized.as_bytes()));
+        return format!(
+            "{}/{}/{}/{}",
+            self.upload_url,
+            &hash[0..1],
+            &hash[0..2],
+            encode_file_name(normalized.as_str())
+        );
+    }
+}
+
+impl Default for HashedPathImageUrlResolver {
+    fn default() -> Self {
+        HashedPathImageUrlResolver::new(WIKIMEDIA_UPLOAD_URL)
+    }
+}
+
+#[async_trait]
+impl ImageUrlResolver for HashedPathImageUrlResolver {
+    async fn resolve(&self, file_name: &str) -> Result<String, String> {
+        Ok(self.image_url(file_name))
+    }
+}
+
+// MediaWikiのタイトル正規化（空白をアンダースコアに、先頭を大文字に）
+fn normalize_file_name(file_name: &str) -> String {
+    let replaced = file_name.trim().replace(" ", "_");
+    let mut chars = replaced.chars();
+    return match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    };
+}
+
+// MediaWikiのwfUrlencodeと同じ文字を残してパーセントエンコードする
+fn encode_file_name(file_name: &str) -> String {
+    let mut encoded = String::new();
+    for byte in file_name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => encoded.push(byte as char),
+            b'-' | b'_' | b'.' | b'~' | b';' | b'@' | b'$' | b'!' | b'*' | b'(' | b')' | b','
+            | b'/' | b':' => encoded.push(byte as char),
+            _ => encoded.push_str(format!("%{:02X}", byte).as_str()),
         }
     }
+    return encoded;
 }
 
 #[cfg(test)]
 mod tests {
     use crate::chapter03::answer::{
         extract_basic_info, extract_categories, extract_category_lines, extract_files,
-        extract_ndjson_from_gzip, extract_sections, get_country_flag_url, load_json, LinkCleaner,
+        extract_ndjson_from_gzip, extract_sections, get_country_flag_url, load_json,
+        ApiImageUrlResolver, HashedPathImageUrlResolver, ImageUrlResolver, LinkCleaner,
         NoneCleaner, Section, StrongCleaner,
     };
+    use crate::chapter03::mediawiki::tests::start_stub_server;
+    use std::collections::HashMap;
 
     const INPUT_PATH: &str = "data/jawiki-country.json.gz";
     const KEYWORD: &str = "イギリス";
@@ -746,11 +811,73 @@ mod tests {
         let article = articles.get(0);
         let expected_url =
             "https://upload.wikimedia.org/wikipedia/en/a/ae/Flag_of_the_United_Kingdom.svg";
-        match get_country_flag_url(extract_basic_info(article.unwrap(), NoneCleaner {})) {
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        match rt.block_on(get_country_flag_url(
+            extract_basic_info(article.unwrap(), NoneCleaner {}),
+            &HashedPathImageUrlResolver::default(),
+        )) {
             None => panic!("Cannot get image url..."),
             Some(url) => {
                 assert_eq!(url, expected_url);
             }
         }
     }
+
+    #[test]
+    pub fn success_29_hashed_path_image_url() {
+        let resolver = HashedPathImageUrlResolver::default();
+        assert_eq!(
+            "https://upload.wikimedia.org/wikipedia/en/a/ae/Flag_of_the_United_Kingdom.svg",
+            resolver.image_url("Flag of the United Kingdom.svg")
+        );
+        assert_eq!(
+            "https://upload.wikimedia.org/wikipedia/en/a/ae/Flag_of_the_United_Kingdom.svg",
+            resolver.image_url("flag of the United Kingdom.svg")
+        );
+        let commons =
+            HashedPathImageUrlResolver::new("https://upload.wikimedia.org/wikipedia/commons/");
+        let url = commons.image_url("Lenepveu, Jeanne d'Arc au siège d'Orléans.jpg");
+        assert!(url.starts_with("https://upload.wikimedia.org/wikipedia/commons/"));
+        assert!(url.ends_with("/Lenepveu,_Jeanne_d%27Arc_au_si%C3%A8ge_d%27Orl%C3%A9ans.jpg"));
+    }
+
+    #[test]
+    pub fn success_29_api_image_url_with_stub_server() {
+        let (api_url, requests) = start_stub_server(vec![(
+            200,
+            r#"{"batchcomplete":true,"query":{"pages":[{"ns":6,"title":"File:Flag of the United Kingdom.svg","missing":true,"known":true,"imageinfo":[{"url":"https://upload.wikimedia.org/wikipedia/en/a/ae/Flag_of_the_United_Kingdom.svg"}]}]}}"#,
+        )]);
+        let resolver = ApiImageUrlResolver::new(api_url.as_str());
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let url = rt
+            .block_on(resolver.resolve("Flag of the United Kingdom.svg"))
+            .expect("Cannot get image url...");
+        assert_eq!(
+            "https://upload.wikimedia.org/wikipedia/en/a/ae/Flag_of_the_United_Kingdom.svg",
+            url
+        );
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].starts_with("GET /w/api.php?"));
+        assert!(requests[0].contains("prop=imageinfo"));
+    }
+
+    #[test]
+    pub fn success_29_unresolved_flag_url() {
+        let (api_url, _) = start_stub_server(vec![(500, "")]);
+        let resolver = ApiImageUrlResolver::new(api_url.as_str());
+        let mut basic_info = HashMap::new();
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        assert_eq!(
+            None,
+            rt.block_on(get_country_flag_url(basic_info.clone(), &resolver))
+        );
+        basic_info.insert(
+            "国旗画像".to_string(),
+            "Flag of the United Kingdom.svg".to_string(),
+        );
+        assert_eq!(
+            None,
+            rt.block_on(get_country_flag_url(basic_info, &resolver))
+        );
+    }
 }
@@ -0,0 +1,361 @@
+use crate::chapter03::answer::{extract_categories, Article};
+use crate::render::{escape_dot, escape_xml};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::Write;
+
+// カテゴリ -> 記事の二部グラフ
+#[derive(Debug, Default)]
+pub struct CategoryGraph {
+    articles: Vec<String>,
+    categories: Vec<String>,
+    article_index: HashMap<String, usize>,
+    category_index: HashMap<String, usize>,
+    // 記事 -> カテゴリ
+    article_edges: Vec<BTreeSet<usize>>,
+    // カテゴリ -> 記事
+    category_edges: Vec<BTreeSet<usize>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SharedCategories<'a> {
+    pub article1: &'a str,
+    pub article2: &'a str,
+    pub categories: Vec<&'a str>,
+}
+
+impl CategoryGraph {
+    pub fn from_articles(articles: &[Article]) -> CategoryGraph {
+        let mut graph = CategoryGraph::default();
+        articles
+            .iter()
+            .for_each(|article| graph.add_article(article));
+        return graph;
+    }
+
+    pub fn add_article(&mut self, article: &Article) {
+        let article_id = self.article_id(article.title());
+        extract_categories(article).iter().for_each(|category| {
+            let category_id = self.category_id(category.trim());
+            self.article_edges[article_id].insert(category_id);
+            self.category_edges[category_id].insert(article_id);
+        });
+    }
+
+    fn article_id(&mut self, title: &str) -> usize {
+        if let Some(id) = self.article_index.get(title) {
+            return *id;
+        }
+        let id = self.articles.len();
+        self.articles.push(title.to_string());
+        self.article_edges.push(BTreeSet::new());
+        self.article_index.insert(title.to_string(), id);
+        return id;
+    }
+
+    fn category_id(&mut self, category: &str) -> usize {
+        if let Some(id) = self.category_index.get(category) {
+            return *id;
+        }
+        let id = self.categories.len();
+        self.categories.push(category.to_string());
+        self.category_edges.push(BTreeSet::new());
+        self.category_index.insert(category.to_string(), id);
+        return id;
+    }
+
+    pub fn article_count(&self) -> usize {
+        self.articles.len()
+    }
+
+    pub fn category_count(&self) -> usize {
+        self.categories.len()
+    }
+
+    pub fn categories_of(&self, title: &str) -> Vec<&str> {
+        match self.article_index.get(title) {
+            Some(id) => self.article_edges[*id]
+                .iter()
+                .map(|category_id| self.categories[*category_id].as_str())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn articles_in(&self, category: &str) -> Vec<&str> {
+        match self.category_index.get(category) {
+            Some(id) => self.category_edges[*id]
+                .iter()
+                .map(|article_id| self.articles[*article_id].as_str())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    // カテゴリの出現頻度（所属記事数の降順、同数はカテゴリ名順）
+    pub fn category_frequency(&self) -> Vec<(&str, usize)> {
+        let mut frequency: Vec<(&str, usize)> = self
+            .categories
+            .iter()
+            .zip(self.category_edges.iter())
+            .map(|(category, articles)| (category.as_str(), articles.len()))
+            .collect();
+        frequency.sort_by(|x, y| y.1.cmp(&x.1).then_with(|| x.0.cmp(y.0)));
+        return frequency;
+    }
+
+    pub fn shared_categories(&self, title1: &str, title2: &str) -> Vec<&str> {
+        match (
+            self.article_index.get(title1),
+            self.article_index.get(title2),
+        ) {
+            (Some(id1), Some(id2)) => self.article_edges[*id1]
+                .intersection(&self.article_edges[*id2])
+                .map(|category_id| self.categories[*category_id].as_str())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    // 共通カテゴリ数の多い記事ペア上位n件
+    pub fn most_shared_pairs(&self, n: usize) -> Vec<SharedCategories<'_>> {
+        let mut pair_counts: HashMap<(usize, usize), usize> = HashMap::new();
+        self.category_edges.iter().for_each(|articles| {
+            let articles: Vec<&usize> = articles.iter().collect();
+            for (i, article1) in articles.iter().enumerate() {
+                for article2 in articles.iter().skip(i + 1) {
+                    *pair_counts.entry((**article1, **article2)).or_insert(0) += 1;
+                }
+            }
+        });
+        let mut pairs: Vec<((usize, usize), usize)> = pair_counts.into_iter().collect();
+        pairs.sort_by(|x, y| {
+            y.1.cmp(&x.1)
+                .then_with(|| self.articles[(x.0).0].cmp(&self.articles[(y.0).0]))
+                .then_with(|| self.articles[(x.0).1].cmp(&self.articles[(y.0).1]))
+        });
+        return pairs
+            .iter()
+            .take(n)
+            .map(|((id1, id2), _)| {
+                let article1 = self.articles[*id1].as_str();
+                let article2 = self.articles[*id2].as_str();
+                SharedCategories {
+                    article1,
+                    article2,
+                    categories: self.shared_categories(article1, article2),
+                }
+            })
+            .collect();
+    }
+
+    // 指定記事と共通カテゴリ数の多い記事上位n件
+    pub fn similar_articles(&self, title: &str, n: usize) -> Vec<(&str, usize)> {
+        let id = match self.article_index.get(title) {
+            Some(id) => *id,
+            None => return vec![],
+        };
+        let mut counts: HashMap<usize, usize> = HashMap::new();
+        self.article_edges[id].iter().for_each(|category_id| {
+            self.category_edges[*category_id]
+                .iter()
+                .filter(|other| **other != id)
+                .for_each(|other| *counts.entry(*other).or_insert(0) += 1);
+        });
+        let mut similar: Vec<(&str, usize)> = counts
+            .into_iter()
+            .map(|(other, count)| (self.articles[other].as_str(), count))
+            .collect();
+        similar.sort_by(|x, y| y.1.cmp(&x.1).then_with(|| x.0.cmp(y.0)));
+        similar.truncate(n);
+        return similar;
+    }
+
+    // Graphviz DOT形式
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("graph categories {\n");
+        self.articles.iter().enumerate().for_each(|(id, title)| {
+            dot.push_str(
+                format!(
+                    "  a{} [label=\"{}\", shape=box];\n",
+                    id,
+                    escape_dot(title.as_str())
+                )
+                .as_str(),
+            );
+        });
+        self.categories
+            .iter()
+            .enumerate()
+            .for_each(|(id, category)| {
+                dot.push_str(
+                    format!(
+                        "  c{} [label=\"{}\", shape=ellipse];\n",
+                        id,
+                        escape_dot(category.as_str())
+                    )
+                    .as_str(),
+                );
+            });
+        self.category_edges
+            .iter()
+            .enumerate()
+            .for_each(|(category_id, articles)| {
+                articles.iter().for_each(|article_id| {
+                    dot.push_str(format!("  c{} -- a{};\n", category_id, article_id).as_str());
+                })
+            });
+        dot.push_str("}\n");
+        return dot;
+    }
+
+    // GraphML形式
+    pub fn to_graphml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        xml.push_str(
+            "  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n",
+        );
+        xml.push_str("  <key id=\"type\" for=\"node\" attr.name=\"type\" attr.type=\"string\"/>\n");
+        xml.push_str("  <graph id=\"categories\" edgedefault=\"undirected\">\n");
+        let node = |id: String, label: &str, node_type: &str| {
+            format!(
+                "    <node id=\"{}\"><data key=\"label\">{}</data><data key=\"type\">{}</data></node>\n",
+                id,
+                escape_xml(label),
+                node_type
+            )
+        };
+        self.articles.iter().enumerate().for_each(|(id, title)| {
+            xml.push_str(node(format!("a{}", id), title.as_str(), "article").as_str());
+        });
+        self.categories
+            .iter()
+            .enumerate()
+            .for_each(|(id, category)| {
+                xml.push_str(node(format!("c{}", id), category.as_str(), "category").as_str());
+            });
+        self.category_edges
+            .iter()
+            .enumerate()
+            .for_each(|(category_id, articles)| {
+                articles.iter().for_each(|article_id| {
+                    xml.push_str(
+                        format!(
+                            "    <edge source=\"c{}\" target=\"a{}\"/>\n",
+                            category_id, article_id
+                        )
+                        .as_str(),
+                    );
+                })
+            });
+        xml.push_str("  </graph>\n</graphml>\n");
+        return xml;
+    }
+
+    pub fn write_dot(&self, output_file_name: &str) {
+        let mut out = File::create(output_file_name).expect("can't create dot file");
+        write!(out, "{}", self.to_dot()).expect("Error during write");
+    }
+
+    pub fn write_graphml(&self, output_file_name: &str) {
+        let mut out = File::create(output_file_name).expect("can't create graphml file");
+        write!(out, "{}", self.to_graphml()).expect("Error during write");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter03::answer::{load_articles, Article};
+    use crate::chapter03::category_graph::{CategoryGraph, SharedCategories};
+
+    const INPUT_PATH: &str = "data/jawiki-country.json.gz";
+
+    fn articles() -> Vec<Article> {
+        vec![
+            Article::new(
+                "イギリス",
+                "本文\n[[Category:イギリス|*]]\n[[Category:島国]]\n[[Category:現存する君主国]]\n[[Category:G8加盟国]]",
+            ),
+            Article::new(
+                "日本",
+                "本文\n[[Category:日本|*]]\n[[Category:島国]]\n[[Category:現存する君主国]]\n[[Category:G8加盟国]]",
+            ),
+            Article::new(
+                "フランス",
+                "本文\n[[Category:フランス|*]]\n[[Category:G8加盟国]]\n[[Category:共和国]]",
+            ),
+            Article::new("アイスランド", "本文\n[[Category:島国]]\n[[Category:共和国]]"),
+        ]
+    }
+
+    #[test]
+    pub fn success_build_graph() {
+        let graph = CategoryGraph::from_articles(&articles());
+        assert_eq!(4, graph.article_count());
+        assert_eq!(7, graph.category_count());
+        assert_eq!(
+            vec!["イギリス", "島国", "現存する君主国", "G8加盟国"],
+            graph.categories_of("イギリス")
+        );
+        assert_eq!(
+            vec!["イギリス", "日本", "アイスランド"],
+            graph.articles_in("島国")
+        );
+        assert!(graph.categories_of("ドイツ").is_empty());
+    }
+
+    #[test]
+    pub fn success_category_frequency() {
+        let graph = CategoryGraph::from_articles(&articles());
+        let frequency = graph.category_frequency();
+        assert_eq!(("G8加盟国", 3), frequency[0]);
+        assert_eq!(("島国", 3), frequency[1]);
+        assert_eq!(("共和国", 2), frequency[2]);
+        assert_eq!(("現存する君主国", 2), frequency[3]);
+    }
+
+    #[test]
+    pub fn success_most_shared_pairs() {
+        let graph = CategoryGraph::from_articles(&articles());
+        let pairs = graph.most_shared_pairs(2);
+        assert_eq!(
+            SharedCategories {
+                article1: "イギリス",
+                article2: "日本",
+                categories: vec!["島国", "現存する君主国", "G8加盟国"],
+            },
+            pairs[0]
+        );
+        assert_eq!(1, pairs[1].categories.len());
+        assert_eq!(
+            vec![("日本", 3), ("アイスランド", 1), ("フランス", 1)],
+            graph.similar_articles("イギリス", 5)
+        );
+    }
+
+    #[test]
+    pub fn success_export_dot_and_graphml() {
+        let graph =
+            CategoryGraph::from_articles(&[Article::new("A&B \"国\"", "[[Category:<島国>]]")]);
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("graph categories {\n"));
+        assert!(dot.contains("  a0 [label=\"A&B \\\"国\\\"\", shape=box];\n"));
+        assert!(dot.contains("  c0 -- a0;\n"));
+        let graphml = graph.to_graphml();
+        assert!(graphml.contains("<data key=\"label\">A&amp;B &quot;国&quot;</data>"));
+        assert!(graphml.contains("<data key=\"label\">&lt;島国&gt;</data>"));
+        assert!(graphml.contains("<edge source=\"c0\" target=\"a0\"/>"));
+    }
+
+    #[test]
+    pub fn success_category_graph_from_dump() {
+        let graph = CategoryGraph::from_articles(&load_articles(INPUT_PATH));
+        assert_eq!(248, graph.article_count());
+        assert!(graph.categories_of("イギリス").contains(&"島国"));
+        graph
+            .most_shared_pairs(10)
+            .iter()
+            .for_each(|pair| println!("{:?}", pair));
+    }
+}
@@ -0,0 +1,381 @@
+use crate::chapter03::answer::Article;
+use regex::Regex;
+use std::cmp::{min, Ordering};
+use std::collections::{BTreeSet, HashMap};
+
+// 記事ではないページへのリンク（名前空間付き）
+const NON_ARTICLE_PREFIXES: [&str; 14] = [
+    "ファイル:",
+    "File:",
+    "Image:",
+    "画像:",
+    "Category:",
+    "カテゴリ:",
+    "Template:",
+    "テンプレート:",
+    "Wikipedia:",
+    "Help:",
+    "Portal:",
+    "プロジェクト:",
+    "wikt:",
+    "Special:",
+];
+
+// 内部リンク
+#[derive(Debug, PartialEq)]
+pub struct Link {
+    pub target: String,
+    pub anchor: String,
+    pub section: Option<String>,
+}
+
+pub fn extract_links(article: &Article) -> Vec<Link> {
+    let re_section = Regex::new(r"^(={2,})\s*([^=]+?)\s*(={2,})").expect("syntax error in regex");
+    let re_link = Regex::new(r"\[\[([^\[\]]+?)\]\]").expect("syntax error in regex");
+    let mut section: Option<String> = None;
+    let mut links = vec![];
+    article.lines_from_text().iter().for_each(|line| {
+        if let Some(cap) = re_section.captures(line) {
+            section = Some(cap[2].to_string());
+        }
+        for cap in re_link.captures_iter(line) {
+            let mut parts = cap[1].splitn(2, '|');
+            let raw_target = parts.next().unwrap_or("").trim();
+            let target = match normalize_target(raw_target) {
+                Some(target) => target,
+                None => continue,
+            };
+            let anchor = match parts.next() {
+                Some(anchor) if !anchor.trim().is_empty() => anchor.trim().to_string(),
+                _ => raw_target.to_string(),
+            };
+            links.push(Link {
+                target,
+                anchor,
+                section: section.clone(),
+            });
+        }
+    });
+    return links;
+}
+
+// リンク先をページ名に正規化する。記事以外へのリンクはNone
+fn normalize_target(raw_target: &str) -> Option<String> {
+    if raw_target.starts_with(':')
+        || NON_ARTICLE_PREFIXES
+            .iter()
+            .any(|prefix| raw_target.starts_with(prefix))
+    {
+        return None;
+    }
+    let without_fragment = raw_target.split('#').next().unwrap_or("").trim();
+    if without_fragment.is_empty() {
+        return None;
+    }
+    let replaced = without_fragment.replace("_", " ");
+    let mut chars = replaced.chars();
+    return chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect());
+}
+
+// 記事間のリンクグラフ（同じリンク先への複数リンクは1本の辺とする）
+#[derive(Debug, Default)]
+pub struct LinkGraph {
+    nodes: Vec<String>,
+    index: HashMap<String, usize>,
+    out_edges: Vec<BTreeSet<usize>>,
+    in_edges: Vec<BTreeSet<usize>>,
+}
+
+impl LinkGraph {
+    // 与えられた記事間のリンクだけでグラフを作る
+    pub fn from_articles(articles: &[Article]) -> LinkGraph {
+        let mut graph = LinkGraph::default();
+        articles.iter().for_each(|article| {
+            graph.node_id(article.title());
+        });
+        articles.iter().for_each(|article| {
+            let source = graph.index[article.title()];
+            extract_links(article).iter().for_each(|link| {
+                if let Some(target) = graph.index.get(link.target.as_str()).cloned() {
+                    graph.add_edge(source, target);
+                }
+            });
+        });
+        return graph;
+    }
+
+    fn node_id(&mut self, title: &str) -> usize {
+        if let Some(id) = self.index.get(title) {
+            return *id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(title.to_string());
+        self.index.insert(title.to_string(), id);
+        self.out_edges.push(BTreeSet::new());
+        self.in_edges.push(BTreeSet::new());
+        return id;
+    }
+
+    fn add_edge(&mut self, source: usize, target: usize) {
+        if source != target {
+            self.out_edges[source].insert(target);
+            self.in_edges[target].insert(source);
+        }
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.out_edges.iter().map(|edges| edges.len()).sum()
+    }
+
+    pub fn in_degree(&self, title: &str) -> usize {
+        self.index
+            .get(title)
+            .map(|id| self.in_edges[*id].len())
+            .unwrap_or(0)
+    }
+
+    pub fn out_degree(&self, title: &str) -> usize {
+        self.index
+            .get(title)
+            .map(|id| self.out_edges[*id].len())
+            .unwrap_or(0)
+    }
+
+    // 被リンク数の降順（同数はタイトル順）
+    pub fn in_degree_ranking(&self) -> Vec<(&str, usize)> {
+        let mut ranking: Vec<(&str, usize)> = self
+            .nodes
+            .iter()
+            .zip(self.in_edges.iter())
+            .map(|(title, edges)| (title.as_str(), edges.len()))
+            .collect();
+        ranking.sort_by(|x, y| y.1.cmp(&x.1).then_with(|| x.0.cmp(y.0)));
+        return ranking;
+    }
+
+    // PageRank（リンクを持たないページのスコアは全ページに均等に配る）
+    pub fn pagerank(&self, damping: f64, iterations: usize) -> Vec<(&str, f64)> {
+        let n = self.nodes.len();
+        if n == 0 {
+            return vec![];
+        }
+        let mut rank = vec![1.0 / n as f64; n];
+        for _ in 0..iterations {
+            let dangling: f64 = (0..n)
+                .filter(|v| self.out_edges[*v].is_empty())
+                .map(|v| rank[v])
+                .sum();
+            let base = (1.0 - damping) / n as f64 + damping * dangling / n as f64;
+            let mut next = vec![base; n];
+            for v in 0..n {
+                let out_degree = self.out_edges[v].len();
+                if out_degree > 0 {
+                    let share = damping * rank[v] / out_degree as f64;
+                    self.out_edges[v].iter().for_each(|w| next[*w] += share);
+                }
+            }
+            let diff: f64 = next
+                .iter()
+                .zip(rank.iter())
+                .map(|(x, y)| (x - y).abs())
+                .sum();
+            rank = next;
+            if diff < 1.0e-10 {
+                break;
+            }
+        }
+        let mut ranking: Vec<(&str, f64)> = self
+            .nodes
+            .iter()
+            .map(|title| title.as_str())
+            .zip(rank.into_iter())
+            .collect();
+        ranking.sort_by(|x, y| {
+            y.1.partial_cmp(&x.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| x.0.cmp(y.0))
+        });
+        return ranking;
+    }
+
+    // 強連結成分（Tarjan）。大きい順、成分内はタイトル順
+    pub fn strongly_connected_components(&self) -> Vec<Vec<&str>> {
+        let n = self.nodes.len();
+        let adjacency: Vec<Vec<usize>> = self
+            .out_edges
+            .iter()
+            .map(|edges| edges.iter().cloned().collect())
+            .collect();
+        let mut counter = 0;
+        let mut indices: Vec<Option<usize>> = vec![None; n];
+        let mut lowlink = vec![0; n];
+        let mut on_stack = vec![false; n];
+        let mut stack: Vec<usize> = vec![];
+        let mut components: Vec<Vec<&str>> = vec![];
+
+        for start in 0..n {
+            if indices[start].is_some() {
+                continue;
+            }
+            // 再帰の代わりに (ノード, 次に調べる隣接ノードの位置) を積む
+            let mut work: Vec<(usize, usize)> = vec![(start, 0)];
+            while let Some((v, position)) = work.pop() {
+                if position == 0 {
+                    indices[v] = Some(counter);
+                    lowlink[v] = counter;
+                    counter += 1;
+                    stack.push(v);
+                    on_stack[v] = true;
+                }
+                let mut descended = false;
+                let mut next = position;
+                while next < adjacency[v].len() {
+                    let w = adjacency[v][next];
+                    next += 1;
+                    match indices[w] {
+                        None => {
+                            work.push((v, next));
+                            work.push((w, 0));
+                            descended = true;
+                            break;
+                        }
+                        Some(index) if on_stack[w] => lowlink[v] = min(lowlink[v], index),
+                        _ => {}
+                    }
+                }
+                if descended {
+                    continue;
+                }
+                if Some(lowlink[v]) == indices[v] {
+                    let mut component = vec![];
+                    while let Some(w) = stack.pop() {
+                        on_stack[w] = false;
+                        component.push(self.nodes[w].as_str());
+                        if w == v {
+                            break;
+                        }
+                    }
+                    component.sort();
+                    components.push(component);
+                }
+                if let Some((parent, _)) = work.last() {
+                    lowlink[*parent] = min(lowlink[*parent], lowlink[v]);
+                }
+            }
+        }
+        components.sort_by(|x, y| y.len().cmp(&x.len()).then_with(|| x.cmp(y)));
+        return components;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter03::answer::{load_articles, Article};
+    use crate::chapter03::link_graph::{extract_links, Link, LinkGraph};
+
+    const INPUT_PATH: &str = "data/jawiki-country.json.gz";
+
+    #[test]
+    pub fn success_extract_links() {
+        let article = Article::new(
+            "イギリス",
+            "'''イギリス'''は[[ヨーロッパ]]の[[島国|島国]]。\n\
+             == 歴史 ==\n\
+             [[フランス#歴史|仏]]と[[:en:France|France]]。\n\
+             [[ファイル:London.jpg|thumb|[[ロンドン]]の風景]]\n\
+             === 近代 ===\n\
+             [[アメリカ合衆国]][[Category:島国]][[#国名|国名]]",
+        );
+        let links = extract_links(&article);
+        let expected = vec![
+            Link {
+                target: "ヨーロッパ".to_string(),
+                anchor: "ヨーロッパ".to_string(),
+                section: None,
+            },
+            Link {
+                target: "島国".to_string(),
+                anchor: "島国".to_string(),
+                section: None,
+            },
+            Link {
+                target: "フランス".to_string(),
+                anchor: "仏".to_string(),
+                section: Some("歴史".to_string()),
+            },
+            Link {
+                target: "ロンドン".to_string(),
+                anchor: "ロンドン".to_string(),
+                section: Some("歴史".to_string()),
+            },
+            Link {
+                target: "アメリカ合衆国".to_string(),
+                anchor: "アメリカ合衆国".to_string(),
+                section: Some("近代".to_string()),
+            },
+        ];
+        assert_eq!(expected, links);
+    }
+
+    fn articles() -> Vec<Article> {
+        vec![
+            Article::new("A", "[[B]] [[C]] [[外部]]"),
+            Article::new("B", "[[A]] [[C]] [[C|再掲]]"),
+            Article::new("C", "[[A]]"),
+            Article::new("D", "[[C]] [[D]]"),
+            Article::new("E", "リンクなし"),
+        ]
+    }
+
+    #[test]
+    pub fn success_degree() {
+        let graph = LinkGraph::from_articles(&articles());
+        assert_eq!(5, graph.node_count());
+        assert_eq!(6, graph.edge_count());
+        assert_eq!(3, graph.in_degree("C"));
+        assert_eq!(2, graph.out_degree("B"));
+        assert_eq!(0, graph.out_degree("E"));
+        assert_eq!(
+            vec![("C", 3), ("A", 2), ("B", 1), ("D", 0), ("E", 0)],
+            graph.in_degree_ranking()
+        );
+    }
+
+    #[test]
+    pub fn success_pagerank() {
+        let graph = LinkGraph::from_articles(&articles());
+        let ranking = graph.pagerank(0.85, 100);
+        let total: f64 = ranking.iter().map(|(_, score)| score).sum();
+        assert!((total - 1.0).abs() < 1.0e-9);
+        let order: Vec<&str> = ranking.iter().map(|(title, _)| *title).collect();
+        assert_eq!(vec!["A", "C", "B", "D", "E"], order);
+        assert!((ranking[3].1 - ranking[4].1).abs() < 1.0e-12);
+    }
+
+    #[test]
+    pub fn success_strongly_connected_components() {
+        let graph = LinkGraph::from_articles(&articles());
+        assert_eq!(
+            vec![vec!["A", "B", "C"], vec!["D"], vec!["E"]],
+            graph.strongly_connected_components()
+        );
+    }
+
+    #[test]
+    pub fn success_link_graph_from_dump() {
+        let graph = LinkGraph::from_articles(&load_articles(INPUT_PATH));
+        assert_eq!(248, graph.node_count());
+        assert!(graph.in_degree("アメリカ合衆国") > 0);
+        graph
+            .pagerank(0.85, 100)
+            .iter()
+            .take(10)
+            .for_each(|(title, score)| println!("{}\t{}", title, score));
+    }
+}
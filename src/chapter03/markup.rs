@@ -0,0 +1,83 @@
+// リンク [[...]] とテンプレート {{...}} の入れ子を数えながら走査する
+
+// open_start（開き括弧 "[[" や "{{" の直後）から、対応する閉じ括弧の位置を探す
+pub fn find_closing(text: &str, open_start: usize, open: u8, close: u8) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 1;
+    let mut i = open_start;
+    while i + 1 < bytes.len() {
+        if bytes[i] == open && bytes[i + 1] == open {
+            depth += 1;
+            i += 2;
+        } else if bytes[i] == close && bytes[i + 1] == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    return None;
+}
+
+// [[...]] や {{...}} の内側を除いて separator で分割する
+pub fn split_top_level<'a>(text: &'a str, separator: &str) -> Vec<&'a str> {
+    let bytes = text.as_bytes();
+    let sep = separator.as_bytes();
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        if i + 1 < bytes.len() {
+            match (bytes[i], bytes[i + 1]) {
+                (b'[', b'[') | (b'{', b'{') => {
+                    depth += 1;
+                    i += 2;
+                    continue;
+                }
+                (b']', b']') | (b'}', b'}') if depth > 0 => {
+                    depth -= 1;
+                    i += 2;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        if depth == 0 && bytes[i..].starts_with(sep) {
+            parts.push(&text[start..i]);
+            i += sep.len();
+            start = i;
+            continue;
+        }
+        i += 1;
+    }
+    parts.push(&text[start..]);
+    return parts;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter03::markup::{find_closing, split_top_level};
+
+    #[test]
+    pub fn success_find_closing() {
+        let text = "[[ファイル:a.jpg|thumb|[[ロンドン]]の風景]]後";
+        let end = find_closing(text, 2, b'[', b']').unwrap();
+        assert_eq!("後", &text[end + 2..]);
+        assert_eq!(Some(9), find_closing("{{a|{{b}}}}", 2, b'{', b'}'));
+        assert_eq!(None, find_closing("[[閉じない", 2, b'[', b']'));
+    }
+
+    #[test]
+    pub fn success_split_top_level() {
+        assert_eq!(
+            vec!["lang", "en", "{{a|b}} [[c|d]]"],
+            split_top_level("lang|en|{{a|b}} [[c|d]]", "|")
+        );
+        assert_eq!(vec!["a ", " b|c"], split_top_level("a || b|c", "||"));
+        assert_eq!(vec!["]]a", "b"], split_top_level("]]a|b", "|"));
+    }
+}
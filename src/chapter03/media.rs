@@ -0,0 +1,247 @@
+use crate::chapter03::answer::{Article, Cleaner};
+use crate::chapter03::markup::{find_closing, split_top_level};
+use regex::Regex;
+
+// メディアファイルを埋め込むときの名前空間（大文字小文字は区別しない）
+const FILE_PREFIXES: [&str; 4] = ["file:", "image:", "ファイル:", "画像:"];
+const ALIGNMENTS: [&str; 8] = [
+    "left", "right", "center", "centre", "none", "左", "右", "中央",
+];
+const FORMATS: [&str; 7] = [
+    "thumb",
+    "thumbnail",
+    "frame",
+    "framed",
+    "frameless",
+    "border",
+    "サムネイル",
+];
+const VERTICAL_ALIGNMENTS: [&str; 8] = [
+    "baseline",
+    "middle",
+    "sub",
+    "super",
+    "text-top",
+    "text-bottom",
+    "top",
+    "bottom",
+];
+
+// メディアファイルへの参照
+#[derive(Debug, Default, PartialEq)]
+pub struct MediaReference {
+    pub file_name: String,
+    pub size: Option<String>,
+    pub alignment: Option<String>,
+    pub format: Option<String>,
+    pub options: Vec<String>,
+    pub caption: Option<String>,
+    pub gallery: bool,
+}
+
+// [[ファイル:...]]、[[File:...]]、[[Image:...]]、[[画像:...]] と <gallery> 内の参照を出現順に抽出する
+pub fn extract_media<T: Cleaner>(article: &Article, cleaner: T) -> Vec<MediaReference> {
+    let text = article.text();
+    let mut references: Vec<(usize, MediaReference)> = vec![];
+
+    let re_gallery =
+        Regex::new(r"(?s)<gallery[^>]*>(.*?)</gallery>").expect("syntax error in regex");
+    let mut galleries = vec![];
+    for cap in re_gallery.captures_iter(text) {
+        let body = cap.get(1).unwrap();
+        galleries.push((cap.get(0).unwrap().start(), cap.get(0).unwrap().end()));
+        let mut offset = body.start();
+        for line in body.as_str().split('\n') {
+            if let Some(mut reference) = parse_gallery_line(line, &cleaner) {
+                reference.gallery = true;
+                references.push((offset, reference));
+            }
+            offset += line.len() + 1;
+        }
+    }
+
+    let mut position = 0;
+    while let Some(found) = text[position..].find("[[") {
+        let start = position + found;
+        let inner_start = start + 2;
+        match find_closing(text, inner_start, b'[', b']') {
+            Some(end) => {
+                let inner = &text[inner_start..end];
+                let in_gallery = galleries
+                    .iter()
+                    .any(|(g_start, g_end)| *g_start <= start && start < *g_end);
+                if !in_gallery {
+                    if let Some(file_name) = strip_file_prefix(inner) {
+                        let params = split_top_level(file_name, "|");
+                        references.push((start, parse_params(params, &cleaner)));
+                    }
+                }
+                // キャプション内の入れ子の参照も拾うため、閉じ括弧ではなく開き括弧の直後から続ける
+                position = inner_start;
+            }
+            None => break,
+        }
+    }
+
+    references.sort_by_key(|(offset, _)| *offset);
+    return references
+        .into_iter()
+        .map(|(_, reference)| reference)
+        .collect();
+}
+
+fn parse_gallery_line<T: Cleaner>(line: &str, cleaner: &T) -> Option<MediaReference> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let without_prefix = strip_file_prefix(line).unwrap_or(line);
+    return Some(parse_params(split_top_level(without_prefix, "|"), cleaner));
+}
+
+fn strip_file_prefix(inner: &str) -> Option<&str> {
+    let trimmed = inner.trim_start();
+    for prefix in FILE_PREFIXES.iter() {
+        if trimmed.len() >= prefix.len()
+            && trimmed.is_char_boundary(prefix.len())
+            && trimmed[..prefix.len()].to_lowercase() == *prefix
+        {
+            return Some(&trimmed[prefix.len()..]);
+        }
+    }
+    return None;
+}
+
+fn parse_params<T: Cleaner>(params: Vec<&str>, cleaner: &T) -> MediaReference {
+    let re_size = Regex::new(r"^(\d+|\d*x\d+)\s*px$").expect("syntax error in regex");
+    let mut reference = MediaReference::default();
+    let mut caption: Option<&str> = None;
+    for (i, param) in params.iter().enumerate() {
+        let param = param.trim();
+        if i == 0 {
+            reference.file_name = param.to_string();
+            continue;
+        }
+        let lower = param.to_lowercase();
+        if re_size.is_match(lower.as_str()) || lower == "upright" || lower.starts_with("upright=") {
+            reference.size = Some(param.to_string());
+        } else if ALIGNMENTS.contains(&lower.as_str()) {
+            reference.alignment = Some(param.to_string());
+        } else if FORMATS.contains(&lower.as_str()) {
+            reference.format = Some(param.to_string());
+        } else if VERTICAL_ALIGNMENTS.contains(&lower.as_str()) || is_named_option(param) {
+            reference.options.push(param.to_string());
+        } else {
+            // 最後の名前なしパラメータがキャプション
+            caption = Some(param);
+        }
+    }
+    reference.caption = caption
+        .map(|caption| cleaner.remove_markup(caption).trim().to_string())
+        .filter(|caption| !caption.is_empty());
+    return reference;
+}
+
+fn is_named_option(param: &str) -> bool {
+    match param.find('=') {
+        Some(pos) => {
+            let key = param[..pos].trim().to_lowercase();
+            ["alt", "link", "page", "lang", "class"].contains(&key.as_str())
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter03::answer::{load_json, Article, LinkCleaner, NoneCleaner, StrongCleaner};
+    use crate::chapter03::media::{extract_media, MediaReference};
+
+    const INPUT_PATH: &str = "data/jawiki-country.json.gz";
+    const KEYWORD: &str = "イギリス";
+
+    #[test]
+    pub fn success_extract_media_with_options() {
+        let article = Article::new(
+            "テスト",
+            "[[ファイル:Royal Coat of Arms of the United Kingdom.svg|85px|イギリスの国章]]\n\
+             [[File:Big_Ben.jpg|thumb|left|upright=0.8|alt=時計塔|'''[[ビッグ・ベン]]'''の夜景]]\n\
+             [[image:UKpop.svg|220px|x100px]]\n\
+             [[画像:Map.png|frameless|中央|[[ロンドン]]と{{lang|en|London}}]]\n\
+             [[:File:Link only.png]] [[ロンドン]]",
+        );
+        let references = extract_media(
+            &article,
+            LinkCleaner {
+                chain: StrongCleaner {},
+            },
+        );
+        assert_eq!(4, references.len());
+        assert_eq!(
+            MediaReference {
+                file_name: "Royal Coat of Arms of the United Kingdom.svg".to_string(),
+                size: Some("85px".to_string()),
+                caption: Some("イギリスの国章".to_string()),
+                ..MediaReference::default()
+            },
+            references[0]
+        );
+        assert_eq!(
+            MediaReference {
+                file_name: "Big_Ben.jpg".to_string(),
+                size: Some("upright=0.8".to_string()),
+                alignment: Some("left".to_string()),
+                format: Some("thumb".to_string()),
+                options: vec!["alt=時計塔".to_string()],
+                caption: Some("ビッグ・ベンの夜景".to_string()),
+                gallery: false,
+            },
+            references[1]
+        );
+        assert_eq!("UKpop.svg", references[2].file_name);
+        assert_eq!(Some("x100px".to_string()), references[2].size);
+        assert_eq!(None, references[2].caption);
+        assert_eq!(Some("中央".to_string()), references[3].alignment);
+        assert_eq!(
+            Some("ロンドンと{{lang|en|London}}".to_string()),
+            references[3].caption
+        );
+    }
+
+    #[test]
+    pub fn success_extract_media_in_gallery() {
+        let article = Article::new(
+            "テスト",
+            "[[ファイル:Before.jpg|説明]]\n\
+             <gallery widths=\"200\">\n\
+             File:London.jpg|[[ロンドン]]\n\
+             Edinburgh.jpg|エディンバラ|alt=城\n\
+             \n\
+             </gallery>\n\
+             [[File:After.jpg]]",
+        );
+        let references = extract_media(&article, NoneCleaner {});
+        let names: Vec<&str> = references.iter().map(|r| r.file_name.as_str()).collect();
+        assert_eq!(
+            vec!["Before.jpg", "London.jpg", "Edinburgh.jpg", "After.jpg"],
+            names
+        );
+        assert!(references[1].gallery);
+        assert_eq!(Some("[[ロンドン]]".to_string()), references[1].caption);
+        assert_eq!(vec!["alt=城".to_string()], references[2].options);
+        assert!(!references[3].gallery);
+    }
+
+    #[test]
+    pub fn success_extract_media_from_dump() {
+        let articles = load_json(INPUT_PATH, KEYWORD);
+        let references = extract_media(&articles[0], NoneCleaner {});
+        let coat_of_arms = references
+            .iter()
+            .find(|r| r.file_name == "Royal Coat of Arms of the United Kingdom.svg")
+            .expect("coat of arms not found");
+        assert_eq!(Some("85px".to_string()), coat_of_arms.size);
+        assert_eq!(Some("イギリスの国章".to_string()), coat_of_arms.caption);
+        assert!(references.len() >= 28);
+    }
+}
@@ -0,0 +1,673 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// MediaWiki API (action=query) のクライアント
+// https://www.mediawiki.org/wiki/API:Query
+pub const WIKIPEDIA_EN_API_URL: &str = "https://en.wikipedia.org/w/api.php";
+pub const WIKIPEDIA_JA_API_URL: &str = "https://ja.wikipedia.org/w/api.php";
+
+// 1リクエストで指定できるtitlesの上限
+const MAX_TITLES_PER_REQUEST: usize = 50;
+
+#[derive(Debug)]
+pub enum MediaWikiError {
+    Request(reqwest::Error),
+    Status(StatusCode),
+    Decode(serde_json::Error),
+    Api { code: String, info: String },
+    NotFound(String),
+}
+
+impl MediaWikiError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            MediaWikiError::Request(error) => error.is_timeout() || error.is_connect(),
+            MediaWikiError::Status(status) => {
+                *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            MediaWikiError::Api { code, .. } => code == "maxlag" || code == "ratelimited",
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for MediaWikiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaWikiError::Request(error) => write!(f, "Error occurred... {}", error),
+            MediaWikiError::Status(status) => write!(f, "Status code is {}.", status),
+            MediaWikiError::Decode(error) => write!(f, "Cannot decode response: {}", error),
+            MediaWikiError::Api { code, info } => write!(f, "API error [{}]: {}", code, info),
+            MediaWikiError::NotFound(title) => write!(f, "Cannot find {}...", title),
+        }
+    }
+}
+
+impl std::error::Error for MediaWikiError {}
+
+impl From<reqwest::Error> for MediaWikiError {
+    fn from(error: reqwest::Error) -> Self {
+        MediaWikiError::Request(error)
+    }
+}
+
+impl From<serde_json::Error> for MediaWikiError {
+    fn from(error: serde_json::Error) -> Self {
+        MediaWikiError::Decode(error)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Prop {
+    ImageInfo,
+    Categories,
+    Revisions,
+    Extracts,
+}
+
+impl Prop {
+    fn name(&self) -> &'static str {
+        match self {
+            Prop::ImageInfo => "imageinfo",
+            Prop::Categories => "categories",
+            Prop::Revisions => "revisions",
+            Prop::Extracts => "extracts",
+        }
+    }
+
+    fn params(&self) -> Vec<(&'static str, &'static str)> {
+        match self {
+            Prop::ImageInfo => vec![("iiprop", "url")],
+            Prop::Categories => vec![("cllimit", "max")],
+            Prop::Revisions => vec![("rvprop", "content"), ("rvslots", "main")],
+            Prop::Extracts => vec![("explaintext", "1"), ("exlimit", "max")],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Page {
+    #[serde(default)]
+    pageid: Option<u64>,
+    #[serde(default)]
+    ns: i64,
+    title: String,
+    #[serde(default)]
+    missing: bool,
+    #[serde(default)]
+    imageinfo: Vec<ImageInfo>,
+    #[serde(default)]
+    categories: Vec<CategoryInfo>,
+    #[serde(default)]
+    revisions: Vec<Revision>,
+    #[serde(default)]
+    extract: Option<String>,
+}
+
+impl Page {
+    pub fn title(&self) -> &str {
+        self.title.as_str()
+    }
+
+    pub fn ns(&self) -> i64 {
+        self.ns
+    }
+
+    pub fn is_missing(&self) -> bool {
+        self.missing
+    }
+
+    pub fn image_url(&self) -> Option<&str> {
+        self.imageinfo.first().map(|info| info.url.as_str())
+    }
+
+    pub fn categories(&self) -> Vec<&str> {
+        self.categories
+            .iter()
+            .map(|category| category.title.as_str())
+            .collect()
+    }
+
+    pub fn content(&self) -> Option<&str> {
+        self.revisions
+            .first()
+            .and_then(|revision| revision.slots.get("main"))
+            .map(|slot| slot.content.as_str())
+    }
+
+    pub fn extract(&self) -> Option<&str> {
+        self.extract.as_ref().map(|extract| extract.as_str())
+    }
+
+    // continueで分割されて返ってきた同じページの情報をまとめる
+    fn merge(&mut self, other: Page) {
+        if self.pageid.is_none() {
+            self.pageid = other.pageid;
+        }
+        self.imageinfo.extend(other.imageinfo);
+        self.categories.extend(other.categories);
+        self.revisions.extend(other.revisions);
+        if self.extract.is_none() {
+            self.extract = other.extract;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct ImageInfo {
+    url: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct CategoryInfo {
+    title: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Revision {
+    #[serde(default)]
+    slots: HashMap<String, Slot>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct Slot {
+    #[serde(default)]
+    content: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse {
+    #[serde(rename = "continue")]
+    continuation: Option<HashMap<String, Value>>,
+    query: Option<QueryResult>,
+    error: Option<ApiError>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct QueryResult {
+    #[serde(default)]
+    normalized: Vec<Normalized>,
+    #[serde(default)]
+    pages: Vec<Page>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Normalized {
+    from: String,
+    to: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    code: String,
+    #[serde(default)]
+    info: String,
+}
+
+// 複数titlesの問い合わせ結果
+#[derive(Debug, Default)]
+pub struct QueryPages {
+    pages: Vec<Page>,
+    normalized: HashMap<String, String>,
+}
+
+impl QueryPages {
+    pub fn pages(&self) -> &Vec<Page> {
+        &self.pages
+    }
+
+    // 問い合わせたタイトル（正規化前でも可）でページを引く
+    pub fn get(&self, title: &str) -> Option<&Page> {
+        let normalized = self
+            .normalized
+            .get(title)
+            .map(|to| to.as_str())
+            .unwrap_or(title);
+        self.pages.iter().find(|page| page.title == normalized)
+    }
+
+    fn add(&mut self, result: QueryResult) {
+        result.normalized.into_iter().for_each(|normalized| {
+            self.normalized.insert(normalized.from, normalized.to);
+        });
+        for page in result.pages {
+            match self.pages.iter_mut().find(|p| p.title == page.title) {
+                Some(existing) => existing.merge(page),
+                None => self.pages.push(page),
+            }
+        }
+    }
+}
+
+// 最低限のリクエスト間隔を守るためのレートリミッタ
+struct RateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    fn new(interval: Duration) -> RateLimiter {
+        RateLimiter {
+            interval,
+            next_slot: Mutex::new(None),
+        }
+    }
+
+    async fn wait(&self) {
+        let wait = {
+            let mut next_slot = self.next_slot.lock().expect("Poisoned rate limiter");
+            let now = Instant::now();
+            let slot = match *next_slot {
+                Some(slot) if slot > now => slot,
+                _ => now,
+            };
+            *next_slot = Some(slot + self.interval);
+            slot - now
+        };
+        if wait > Duration::from_millis(0) {
+            tokio::time::delay_for(wait).await;
+        }
+    }
+}
+
+pub struct MediaWikiClient {
+    client: reqwest::Client,
+    api_url: String,
+    rate_limiter: RateLimiter,
+    max_retries: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    batch_size: usize,
+}
+
+impl MediaWikiClient {
+    pub fn new(api_url: &str) -> MediaWikiClient {
+        let client = reqwest::Client::builder()
+            .user_agent("nlp100-rust (https://github.com/johtani/nlp100-rust)")
+            .build()
+            .expect("Fail initializing http client");
+        MediaWikiClient {
+            client,
+            api_url: api_url.to_string(),
+            rate_limiter: RateLimiter::new(Duration::from_millis(100)),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            batch_size: MAX_TITLES_PER_REQUEST,
+        }
+    }
+
+    pub fn with_rate_limit(mut self, interval: Duration) -> MediaWikiClient {
+        self.rate_limiter = RateLimiter::new(interval);
+        self
+    }
+
+    pub fn with_retry(mut self, max_retries: u32, initial_backoff: Duration) -> MediaWikiClient {
+        self.max_retries = max_retries;
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = std::cmp::max(self.max_backoff, initial_backoff);
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> MediaWikiClient {
+        self.batch_size = batch_size.max(1).min(MAX_TITLES_PER_REQUEST);
+        self
+    }
+
+    // titlesをbatch_sizeごとに分割し、continueを辿って全結果を取得する
+    pub async fn query(
+        &self,
+        titles: &[&str],
+        props: &[Prop],
+    ) -> Result<QueryPages, MediaWikiError> {
+        let mut pages = QueryPages::default();
+        for batch in titles.chunks(self.batch_size) {
+            let mut params: Vec<(String, String)> = vec![
+                ("action".to_string(), "query".to_string()),
+                ("format".to_string(), "json".to_string()),
+                ("formatversion".to_string(), "2".to_string()),
+                ("titles".to_string(), batch.join("|")),
+                (
+                    "prop".to_string(),
+                    props
+                        .iter()
+                        .map(|prop| prop.name())
+                        .collect::<Vec<&str>>()
+                        .join("|"),
+                ),
+            ];
+            props.iter().for_each(|prop| {
+                prop.params().iter().for_each(|(key, value)| {
+                    params.push((key.to_string(), value.to_string()));
+                })
+            });
+
+            let mut continuation: HashMap<String, Value> = HashMap::new();
+            loop {
+                let mut request = params.clone();
+                continuation.iter().for_each(|(key, value)| {
+                    let value = match value {
+                        Value::String(value) => value.to_string(),
+                        other => other.to_string(),
+                    };
+                    request.push((key.to_string(), value));
+                });
+                let response = self.get(&request).await?;
+                if let Some(result) = response.query {
+                    pages.add(result);
+                }
+                match response.continuation {
+                    Some(next) => continuation = next,
+                    None => break,
+                }
+            }
+        }
+        return Ok(pages);
+    }
+
+    pub async fn image_url(&self, file_name: &str) -> Result<String, MediaWikiError> {
+        let urls = self.image_urls(&[file_name]).await?;
+        return match urls.get(file_name) {
+            Some(url) => Ok(url.to_string()),
+            None => Err(MediaWikiError::NotFound(file_title(file_name))),
+        };
+    }
+
+    // ファイル名 -> URL。URLが取れなかったファイルは含まない
+    pub async fn image_urls(
+        &self,
+        file_names: &[&str],
+    ) -> Result<HashMap<String, String>, MediaWikiError> {
+        let titles: Vec<String> = file_names.iter().map(|name| file_title(name)).collect();
+        let pages = self
+            .query(
+                &titles.iter().map(|t| t.as_str()).collect::<Vec<&str>>(),
+                &[Prop::ImageInfo],
+            )
+            .await?;
+        let mut urls = HashMap::new();
+        file_names
+            .iter()
+            .zip(titles.iter())
+            .for_each(|(name, title)| {
+                if let Some(url) = pages.get(title).and_then(|page| page.image_url()) {
+                    urls.insert(name.to_string(), url.to_string());
+                }
+            });
+        return Ok(urls);
+    }
+
+    pub async fn categories(&self, title: &str) -> Result<Vec<String>, MediaWikiError> {
+        let pages = self.query(&[title], &[Prop::Categories]).await?;
+        return match pages.get(title) {
+            Some(page) if !page.missing => Ok(page
+                .categories()
+                .iter()
+                .map(|category| category.to_string())
+                .collect()),
+            _ => Err(MediaWikiError::NotFound(title.to_string())),
+        };
+    }
+
+    pub async fn wikitext(&self, title: &str) -> Result<String, MediaWikiError> {
+        let pages = self.query(&[title], &[Prop::Revisions]).await?;
+        return match pages.get(title).and_then(|page| page.content()) {
+            Some(content) => Ok(content.to_string()),
+            None => Err(MediaWikiError::NotFound(title.to_string())),
+        };
+    }
+
+    pub async fn extract(&self, title: &str) -> Result<String, MediaWikiError> {
+        let pages = self.query(&[title], &[Prop::Extracts]).await?;
+        return match pages.get(title).and_then(|page| page.extract()) {
+            Some(extract) => Ok(extract.to_string()),
+            None => Err(MediaWikiError::NotFound(title.to_string())),
+        };
+    }
+
+    async fn get(&self, params: &[(String, String)]) -> Result<ApiResponse, MediaWikiError> {
+        let mut retries = 0;
+        let mut backoff = self.initial_backoff;
+        loop {
+            self.rate_limiter.wait().await;
+            match self.send(params).await {
+                Err(error) if error.is_retryable() && retries < self.max_retries => {
+                    retries += 1;
+                    tokio::time::delay_for(backoff).await;
+                    backoff = std::cmp::min(backoff * 2, self.max_backoff);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send(&self, params: &[(String, String)]) -> Result<ApiResponse, MediaWikiError> {
+        let response = self
+            .client
+            .get(self.api_url.as_str())
+            .query(params)
+            .send()
+            .await?;
+        let status = response.status();
+        if status != StatusCode::OK {
+            return Err(MediaWikiError::Status(status));
+        }
+        let body = response.text().await?;
+        let api_response: ApiResponse = serde_json::from_str(body.as_str())?;
+        return match api_response.error {
+            Some(error) => Err(MediaWikiError::Api {
+                code: error.code,
+                info: error.info,
+            }),
+            None => Ok(api_response),
+        };
+    }
+}
+
+fn file_title(file_name: &str) -> String {
+    format!("File:{}", file_name)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::chapter03::mediawiki::{MediaWikiClient, MediaWikiError, Prop};
+    use reqwest::StatusCode;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // 順番に固定のレスポンスを返すローカルサーバ。受け取ったリクエスト行を記録する
+    pub fn start_stub_server(
+        responses: Vec<(u16, &'static str)>,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Fail binding stub server");
+        let addr = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let recorder = requests.clone();
+        thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().expect("Fail accepting connection");
+                let mut buf = [0; 4096];
+                let mut request = vec![];
+                while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                    let size = stream.read(&mut buf).expect("Fail reading request");
+                    if size == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..size]);
+                }
+                let request = String::from_utf8_lossy(&request).to_string();
+                let request_line = request.lines().next().unwrap_or("").to_string();
+                recorder.lock().unwrap().push(request_line);
+                let response = format!(
+                    "HTTP/1.1 {} STUB\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        return (format!("http://{}/w/api.php", addr), requests);
+    }
+
+    fn test_client(api_url: &str) -> MediaWikiClient {
+        MediaWikiClient::new(api_url)
+            .with_rate_limit(Duration::from_millis(0))
+            .with_retry(2, Duration::from_millis(10))
+    }
+
+    #[test]
+    pub fn success_query_with_continuation() {
+        let (api_url, requests) = start_stub_server(vec![
+            (
+                200,
+                r#"{"continue":{"clcontinue":"23560|G8加盟国","continue":"||"},"query":{"pages":[{"pageid":23560,"ns":0,"title":"イギリス","categories":[{"ns":14,"title":"Category:イギリス"}]}]}}"#,
+            ),
+            (
+                200,
+                r#"{"batchcomplete":true,"query":{"pages":[{"pageid":23560,"ns":0,"title":"イギリス","categories":[{"ns":14,"title":"Category:G8加盟国"},{"ns":14,"title":"Category:島国"}]}]}}"#,
+            ),
+        ]);
+        let client = test_client(api_url.as_str());
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let categories = rt.block_on(client.categories("イギリス")).unwrap();
+        assert_eq!(
+            vec!["Category:イギリス", "Category:G8加盟国", "Category:島国"],
+            categories
+        );
+        let requests = requests.lock().unwrap();
+        assert_eq!(2, requests.len());
+        assert!(!requests[0].contains("clcontinue="));
+        assert!(requests[1].contains("clcontinue="));
+    }
+
+    #[test]
+    pub fn success_batch_image_urls() {
+        let (api_url, requests) = start_stub_server(vec![
+            (
+                200,
+                r#"{"batchcomplete":true,"query":{"normalized":[{"fromencoded":false,"from":"File:flag of Japan.svg","to":"File:Flag of Japan.svg"}],"pages":[{"ns":6,"title":"File:Flag of Japan.svg","missing":true,"known":true,"imageinfo":[{"url":"https://upload.wikimedia.org/wikipedia/en/9/9e/Flag_of_Japan.svg"}]},{"ns":6,"title":"File:Flag of the United Kingdom.svg","missing":true,"known":true,"imageinfo":[{"url":"https://upload.wikimedia.org/wikipedia/en/a/ae/Flag_of_the_United_Kingdom.svg"}]}]}}"#,
+            ),
+            (
+                200,
+                r#"{"batchcomplete":true,"query":{"pages":[{"ns":6,"title":"File:No such file.svg","missing":true}]}}"#,
+            ),
+        ]);
+        let client = test_client(api_url.as_str()).with_batch_size(2);
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let urls = rt
+            .block_on(client.image_urls(&[
+                "flag of Japan.svg",
+                "Flag of the United Kingdom.svg",
+                "No such file.svg",
+            ]))
+            .unwrap();
+        assert_eq!(2, urls.len());
+        assert_eq!(
+            "https://upload.wikimedia.org/wikipedia/en/9/9e/Flag_of_Japan.svg",
+            urls["flag of Japan.svg"]
+        );
+        assert_eq!(
+            "https://upload.wikimedia.org/wikipedia/en/a/ae/Flag_of_the_United_Kingdom.svg",
+            urls["Flag of the United Kingdom.svg"]
+        );
+        assert_eq!(2, requests.lock().unwrap().len());
+    }
+
+    #[test]
+    pub fn success_wikitext_and_extract() {
+        let (api_url, _) = start_stub_server(vec![
+            (
+                200,
+                r#"{"batchcomplete":true,"query":{"pages":[{"pageid":23560,"ns":0,"title":"イギリス","revisions":[{"slots":{"main":{"contentmodel":"wikitext","contentformat":"text/x-wiki","content":"'''イギリス'''は[[島国]]。"}}}]}]}}"#,
+            ),
+            (
+                200,
+                r#"{"batchcomplete":true,"query":{"pages":[{"pageid":23560,"ns":0,"title":"イギリス","extract":"イギリスは島国。"}]}}"#,
+            ),
+        ]);
+        let client = test_client(api_url.as_str());
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        assert_eq!(
+            "'''イギリス'''は[[島国]]。",
+            rt.block_on(client.wikitext("イギリス")).unwrap()
+        );
+        assert_eq!(
+            "イギリスは島国。",
+            rt.block_on(client.extract("イギリス")).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn success_retry_after_server_error() {
+        let (api_url, requests) = start_stub_server(vec![
+            (503, "{}"),
+            (
+                200,
+                r#"{"query":{"pages":[{"ns":6,"title":"File:Flag of Japan.svg","imageinfo":[{"url":"https://upload.wikimedia.org/wikipedia/en/9/9e/Flag_of_Japan.svg"}]}]}}"#,
+            ),
+        ]);
+        let client = test_client(api_url.as_str());
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let url = rt.block_on(client.image_url("Flag of Japan.svg")).unwrap();
+        assert_eq!(
+            "https://upload.wikimedia.org/wikipedia/en/9/9e/Flag_of_Japan.svg",
+            url
+        );
+        assert_eq!(2, requests.lock().unwrap().len());
+    }
+
+    #[test]
+    pub fn fail_after_retries_exhausted() {
+        let (api_url, requests) = start_stub_server(vec![(503, "{}"), (503, "{}"), (503, "{}")]);
+        let client = test_client(api_url.as_str());
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        match rt.block_on(client.image_url("Flag of Japan.svg")) {
+            Err(MediaWikiError::Status(status)) => {
+                assert_eq!(StatusCode::SERVICE_UNAVAILABLE, status)
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(3, requests.lock().unwrap().len());
+    }
+
+    #[test]
+    pub fn fail_with_api_error() {
+        let (api_url, requests) = start_stub_server(vec![(
+            200,
+            r#"{"error":{"code":"badvalue","info":"Unrecognized value for parameter \"prop\"."}}"#,
+        )]);
+        let client = test_client(api_url.as_str());
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        match rt.block_on(client.query(&["イギリス"], &[Prop::Categories])) {
+            Err(MediaWikiError::Api { code, .. }) => assert_eq!("badvalue", code),
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert_eq!(1, requests.lock().unwrap().len());
+    }
+
+    #[test]
+    pub fn success_rate_limit() {
+        let (api_url, _) = start_stub_server(vec![
+            (200, r#"{"query":{"pages":[{"ns":0,"title":"A"}]}}"#),
+            (200, r#"{"query":{"pages":[{"ns":0,"title":"B"}]}}"#),
+        ]);
+        let client =
+            MediaWikiClient::new(api_url.as_str()).with_rate_limit(Duration::from_millis(200));
+        let mut rt = tokio::runtime::Runtime::new().unwrap();
+        let start = Instant::now();
+        rt.block_on(client.query(&["A"], &[Prop::Categories]))
+            .unwrap();
+        rt.block_on(client.query(&["B"], &[Prop::Categories]))
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,270 @@
+use crate::chapter03::answer::{Article, Cleaner, StrongCleaner};
+use crate::chapter03::markup::{find_closing, split_top_level};
+use crate::sentence::split_sentences;
+use regex::Regex;
+use std::fs::File;
+use std::io::Write;
+
+// 本文に残さないリンク（ファイル・カテゴリ）
+const DROPPED_LINK_PREFIXES: [&str; 6] = [
+    "ファイル:",
+    "file:",
+    "image:",
+    "画像:",
+    "category:",
+    "カテゴリ:",
+];
+
+// 段落 -> 文
+#[derive(Debug, Default, PartialEq)]
+pub struct PlainText {
+    paragraphs: Vec<Vec<String>>,
+}
+
+impl PlainText {
+    pub fn paragraphs(&self) -> &Vec<Vec<String>> {
+        &self.paragraphs
+    }
+
+    pub fn sentences(&self) -> Vec<&str> {
+        self.paragraphs
+            .iter()
+            .flat_map(|paragraph| paragraph.iter().map(|sentence| sentence.as_str()))
+            .collect()
+    }
+
+    // 1行1文、段落の間は空行
+    pub fn to_text(&self) -> String {
+        self.paragraphs
+            .iter()
+            .map(|paragraph| format!("{}\n", paragraph.join("\n")))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn write(&self, output_file_name: &str) {
+        let mut out = File::create(output_file_name).expect("can't create text file");
+        write!(out, "{}", self.to_text()).expect("Error during write");
+    }
+}
+
+// テンプレート・脚注・表・カテゴリ・ファイルを除いた本文
+pub fn to_plain_text(article: &Article) -> PlainText {
+    let re_comment = Regex::new(r"(?s)<!--.*?-->").expect("syntax error in regex");
+    let re_ref =
+        Regex::new(r"(?s)<ref[^>]*?/>|<ref[^>]*>.*?</ref>").expect("syntax error in regex");
+    let re_block = Regex::new(r"(?s)<(gallery|math|source|syntaxhighlight)[^>]*>.*?</(gallery|math|source|syntaxhighlight)>")
+        .expect("syntax error in regex");
+    let text = re_comment.replace_all(article.text(), "");
+    let text = re_ref.replace_all(text.as_ref(), "");
+    let text = re_block.replace_all(text.as_ref(), "");
+    let text = render_templates(text.as_ref());
+    let text = remove_tables(text.as_str());
+    let text = render_links(text.as_str());
+    let text = render_inline(text.as_str());
+
+    let re_heading = Regex::new(r"^=+.*=+$").expect("syntax error in regex");
+    let mut paragraphs: Vec<Vec<String>> = vec![];
+    let mut current = String::new();
+    let mut flush = |current: &mut String| {
+        let sentences: Vec<String> = split_sentences(current.as_str())
+            .iter()
+            .map(|sentence| sentence.text.to_string())
+            .collect();
+        if !sentences.is_empty() {
+            paragraphs.push(sentences);
+        }
+        current.clear();
+    };
+    for line in text.split('\n') {
+        let line = line.trim();
+        if line.is_empty() || re_heading.is_match(line) || line.starts_with("__") {
+            flush(&mut current);
+        } else if line.starts_with(|c: char| c == '*' || c == '#' || c == ':' || c == ';') {
+            // 箇条書きは1項目を1段落にする
+            flush(&mut current);
+            current.push_str(
+                line.trim_start_matches(|c: char| c == '*' || c == '#' || c == ':' || c == ';')
+                    .trim(),
+            );
+            flush(&mut current);
+        } else {
+            join_line(&mut current, line);
+        }
+    }
+    flush(&mut current);
+    return PlainText { paragraphs };
+}
+
+// 同じ段落の行をつなぐ。どちらかが日本語なら空白を入れない
+fn join_line(current: &mut String, line: &str) {
+    let needs_space = match (current.chars().last(), line.chars().next()) {
+        (Some(last), Some(first)) => last.is_ascii() && first.is_ascii(),
+        _ => false,
+    };
+    if needs_space {
+        current.push(' ');
+    }
+    current.push_str(line);
+}
+
+// {{lang|en|...}} と {{仮リンク|...}} は表示される文字列を残し、それ以外のテンプレートは除く
+fn render_templates(text: &str) -> String {
+    let mut rendered = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        match find_closing(rest, start + 2, b'{', b'}') {
+            Some(end) => {
+                let params = split_top_level(&rest[start + 2..end], "|");
+                let name = params[0].trim().to_lowercase();
+                if name == "lang" && params.len() >= 3 {
+                    rendered.push_str(render_templates(params[params.len() - 1]).as_str());
+                } else if name == "仮リンク" && params.len() >= 2 {
+                    rendered.push_str(render_templates(params[1]).as_str());
+                }
+                rest = &rest[end + 2..];
+            }
+            None => rest = &rest[start + 2..],
+        }
+    }
+    rendered.push_str(rest);
+    return rendered;
+}
+
+fn remove_tables(text: &str) -> String {
+    let mut depth = 0;
+    let mut lines = vec![];
+    for line in text.split('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("{|") {
+            depth += 1;
+        } else if depth > 0 && trimmed.starts_with("|}") {
+            depth -= 1;
+            // 表の位置で段落を区切る
+            lines.push("");
+        } else if depth == 0 {
+            lines.push(line);
+        }
+    }
+    return lines.join("\n");
+}
+
+// 内部リンクは表示文字列に置き換え、ファイルとカテゴリは除く
+fn render_links(text: &str) -> String {
+    let mut rendered = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        rendered.push_str(&rest[..start]);
+        match find_closing(rest, start + 2, b'[', b']') {
+            Some(end) => {
+                let inner = &rest[start + 2..end];
+                let target = inner.trim_start().trim_start_matches(':').to_lowercase();
+                let dropped = DROPPED_LINK_PREFIXES
+                    .iter()
+                    .any(|prefix| target.starts_with(prefix));
+                if !dropped {
+                    let params = split_top_level(inner, "|");
+                    let display = params[params.len() - 1];
+                    let display = if params.len() == 1 {
+                        display.split('#').next().unwrap_or(display)
+                    } else {
+                        display
+                    };
+                    rendered.push_str(render_links(display).as_str());
+                }
+                rest = &rest[end + 2..];
+            }
+            None => rest = &rest[start + 2..],
+        }
+    }
+    rendered.push_str(rest);
+    return rendered;
+}
+
+// 強調・外部リンク・HTMLタグ・文字実体参照
+fn render_inline(text: &str) -> String {
+    let re_external =
+        Regex::new(r"\[(?:https?:)?//[^\s\]]+\s*([^\]]*)\]").expect("syntax error in regex");
+    let re_br = Regex::new(r"(?i)<br\s*/?>").expect("syntax error in regex");
+    let re_tag = Regex::new(r"</?[a-zA-Z][^>]*>").expect("syntax error in regex");
+    let text = StrongCleaner {}.remove_markup(text);
+    let text = re_external.replace_all(text.as_str(), "$1");
+    let text = re_br.replace_all(text.as_ref(), " ");
+    let text = re_tag.replace_all(text.as_ref(), "");
+    return text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter03::answer::{load_json, Article};
+    use crate::chapter03::plain_text::to_plain_text;
+    use std::fs::create_dir_all;
+
+    const INPUT_PATH: &str = "data/jawiki-country.json.gz";
+    const KEYWORD: &str = "イギリス";
+    const TMP_PATH: &str = "data/chap03_tmp/";
+
+    #[test]
+    pub fn success_to_plain_text() {
+        let article = Article::new(
+            "イギリス",
+            "{{基礎情報 国\n\
+             |略名 = イギリス\n\
+             |国旗画像 = Flag of the United Kingdom.svg\n\
+             }}\n\
+             '''グレートブリテン及び北アイルランド連合王国'''（{{lang|en|United Kingdom}}）は、\n\
+             [[ヨーロッパ]]大陸の北西岸に位置する[[島国|島国]]である<ref>出典</ref>。\
+             首都は[[ロンドン]]<ref name=\"a\" />。\n\
+             \n\
+             == 歴史 ==\n\
+             [[ファイル:London.jpg|thumb|[[ロンドン]]の風景]]\n\
+             {{仮リンク|リンゼイ・ホイル|en|Lindsay Hoyle}}が議長。<!-- コメント -->詳細は[http://example.com 外部サイト]を参照。\n\
+             {| class=\"wikitable\"\n\
+             | 表 || 除外\n\
+             |}\n\
+             * 項目1\n\
+             * [[:en:England|England]] &amp; Wales\n\
+             [[Category:島国]]",
+        );
+        let plain = to_plain_text(&article);
+        assert_eq!(
+            &vec![
+                vec![
+                    "グレートブリテン及び北アイルランド連合王国（United Kingdom）は、ヨーロッパ大陸の北西岸に位置する島国である。".to_string(),
+                    "首都はロンドン。".to_string(),
+                ],
+                vec![
+                    "リンゼイ・ホイルが議長。".to_string(),
+                    "詳細は外部サイトを参照。".to_string(),
+                ],
+                vec!["項目1".to_string()],
+                vec!["England & Wales".to_string()],
+            ],
+            plain.paragraphs()
+        );
+        assert_eq!(6, plain.sentences().len());
+        assert_eq!(
+            "グレートブリテン及び北アイルランド連合王国（United Kingdom）は、ヨーロッパ大陸の北西岸に位置する島国である。\n首都はロンドン。\n\nリンゼイ・ホイルが議長。\n詳細は外部サイトを参照。\n\n項目1\n\nEngland & Wales\n",
+            plain.to_text()
+        );
+    }
+
+    #[test]
+    pub fn success_plain_text_from_dump() {
+        let articles = load_json(INPUT_PATH, KEYWORD);
+        let plain = to_plain_text(&articles[0]);
+        assert!(!plain.sentences().is_empty());
+        plain
+            .sentences()
+            .iter()
+            .for_each(|sentence| assert!(!sentence.contains("[[") && !sentence.contains("{{")));
+        create_dir_all(TMP_PATH).expect("can't create tmp dir");
+        plain.write(format!("{}{}", TMP_PATH, "england_plain.txt").as_str());
+    }
+}
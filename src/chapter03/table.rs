@@ -0,0 +1,438 @@
+use crate::chapter03::answer::{Article, Cleaner};
+use crate::chapter03::markup::{find_closing, split_top_level};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+
+// {| ... |} 形式の表
+#[derive(Debug, Default, PartialEq)]
+pub struct Table {
+    pub caption: Option<String>,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    // タブ区切り（chapter02のコマンドでそのまま扱える形式）
+    pub fn to_tsv(&self) -> String {
+        let mut tsv = String::new();
+        if !self.headers.is_empty() {
+            tsv.push_str(tsv_line(&self.headers).as_str());
+        }
+        self.rows
+            .iter()
+            .for_each(|row| tsv.push_str(tsv_line(row).as_str()));
+        return tsv;
+    }
+
+    pub fn write_tsv(&self, output_file_name: &str) {
+        let mut out = File::create(output_file_name).expect("can't create tsv file");
+        write!(out, "{}", self.to_tsv()).expect("Error during write");
+    }
+}
+
+fn tsv_line(cells: &Vec<String>) -> String {
+    let cells: Vec<String> = cells
+        .iter()
+        .map(|cell| cell.replace("\t", " ").replace("\n", " "))
+        .collect();
+    format!("{}\n", cells.join("\t"))
+}
+
+struct RawCell {
+    text: String,
+    header: bool,
+    rowspan: usize,
+    colspan: usize,
+}
+
+#[derive(Default)]
+struct TableBuilder {
+    caption: Option<String>,
+    rows: Vec<Vec<RawCell>>,
+    // 複数行にまたがるセル・キャプションの続きを受け取る先
+    in_caption: bool,
+}
+
+impl TableBuilder {
+    fn new_row(&mut self) {
+        self.in_caption = false;
+        if self.rows.last().map(|row| !row.is_empty()).unwrap_or(true) {
+            self.rows.push(vec![]);
+        }
+    }
+
+    fn add_cells(&mut self, line: &str, header: bool) {
+        self.in_caption = false;
+        if self.rows.is_empty() {
+            self.rows.push(vec![]);
+        }
+        let separator = if header { "!!" } else { "||" };
+        let cells = split_top_level(line, separator)
+            .into_iter()
+            .flat_map(|cell| {
+                if header {
+                    split_top_level(cell, "||")
+                } else {
+                    vec![cell]
+                }
+            })
+            .map(|cell| parse_cell(cell, header));
+        self.rows.last_mut().unwrap().extend(cells);
+    }
+
+    fn append_text(&mut self, line: &str) {
+        if self.in_caption {
+            if let Some(caption) = self.caption.as_mut() {
+                caption.push('\n');
+                caption.push_str(line);
+            }
+        } else if let Some(cell) = self.rows.last_mut().and_then(|row| row.last_mut()) {
+            cell.text.push('\n');
+            cell.text.push_str(line);
+        }
+    }
+
+    fn build<T: Cleaner>(self, cleaner: &T) -> Table {
+        let clean = |text: &str| {
+            cleaner
+                .remove_markup(link_labels(text.trim()).as_str())
+                .trim()
+                .to_string()
+        };
+        let rows: Vec<Vec<RawCell>> = self
+            .rows
+            .into_iter()
+            .filter(|row| !row.is_empty())
+            .collect();
+        // 先頭から続く、見出しセルだけの行を見出しとする
+        let header_rows = rows
+            .iter()
+            .take_while(|row| row.iter().all(|cell| cell.header))
+            .count();
+
+        // rowspan/colspanを展開して矩形にする
+        let mut grid: Vec<Vec<String>> = vec![];
+        let mut carry: HashMap<usize, (usize, String)> = HashMap::new();
+        for raw_row in rows.iter() {
+            let mut row = vec![];
+            let mut cells = raw_row.iter();
+            let mut col = 0;
+            loop {
+                if let Some((remaining, text)) = carry.get_mut(&col) {
+                    row.push(text.to_string());
+                    *remaining -= 1;
+                    if *remaining == 0 {
+                        carry.remove(&col);
+                    }
+                    col += 1;
+                    continue;
+                }
+                match cells.next() {
+                    Some(cell) => {
+                        let text = clean(cell.text.as_str());
+                        for _ in 0..cell.colspan {
+                            if cell.rowspan > 1 {
+                                carry.insert(col, (cell.rowspan - 1, text.to_string()));
+                            }
+                            row.push(text.to_string());
+                            col += 1;
+                        }
+                    }
+                    None => {
+                        if carry.keys().any(|c| *c > col) {
+                            row.push(String::new());
+                            col += 1;
+                        } else {
+                            break;
+                        }
+                    }
+                }
+            }
+            grid.push(row);
+        }
+
+        let mut grid = grid.into_iter();
+        let headers = merge_headers(grid.by_ref().take(header_rows).collect());
+        return Table {
+            caption: self
+                .caption
+                .map(|caption| clean(caption.as_str()))
+                .filter(|caption| !caption.is_empty()),
+            headers,
+            rows: grid.collect(),
+        };
+    }
+}
+
+// 複数行の見出しは列ごとに上から空白でつなぐ。rowspan で続く同じ見出しは1つにまとめる
+//   国 | 人口 | 人口         国 | 人口 2001 | 人口 2011
+//   国 | 2001 | 2011   ->
+fn merge_headers(header_rows: Vec<Vec<String>>) -> Vec<String> {
+    let width = header_rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    return (0..width)
+        .map(|col| {
+            let mut names: Vec<&str> = vec![];
+            for name in header_rows.iter().filter_map(|row| row.get(col)) {
+                if !name.is_empty() && names.last() != Some(&name.as_str()) {
+                    names.push(name);
+                }
+            }
+            names.join(" ")
+        })
+        .collect();
+}
+
+// 記事中の表を出現順に抽出する（入れ子の表は別の表として扱う）
+pub fn extract_tables<T: Cleaner>(article: &Article, cleaner: T) -> Vec<Table> {
+    let mut tables: Vec<(usize, Table)> = vec![];
+    let mut stack: Vec<(usize, TableBuilder)> = vec![];
+    let mut order = 0;
+    for line in article.lines_from_text() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("{|") {
+            stack.push((order, TableBuilder::default()));
+            order += 1;
+            continue;
+        }
+        let builder = match stack.last_mut() {
+            Some((_, builder)) => builder,
+            None => continue,
+        };
+        if trimmed.starts_with("|}") {
+            let (index, builder) = stack.pop().unwrap();
+            tables.push((index, builder.build(&cleaner)));
+        } else if trimmed.starts_with("|+") {
+            builder.caption = Some(strip_attributes(&trimmed[2..]).to_string());
+            builder.in_caption = true;
+        } else if trimmed.starts_with("|-") {
+            builder.new_row();
+        } else if trimmed.starts_with('!') {
+            builder.add_cells(&trimmed[1..], true);
+        } else if trimmed.starts_with('|') {
+            builder.add_cells(&trimmed[1..], false);
+        } else {
+            builder.append_text(line.as_str());
+        }
+    }
+    tables.sort_by_key(|(index, _)| *index);
+    return tables.into_iter().map(|(_, table)| table).collect();
+}
+
+fn parse_cell(cell: &str, header: bool) -> RawCell {
+    let re_rowspan = Regex::new(r#"(?i)rowspan\s*=\s*"?(\d+)"#).expect("syntax error in regex");
+    let re_colspan = Regex::new(r#"(?i)colspan\s*=\s*"?(\d+)"#).expect("syntax error in regex");
+    let (attributes, text) = split_attributes(cell);
+    let span = |re: &Regex| {
+        attributes
+            .and_then(|attributes| re.captures(attributes))
+            .and_then(|cap| cap[1].parse::<usize>().ok())
+            .filter(|span| *span > 0)
+            .unwrap_or(1)
+    };
+    RawCell {
+        text: text.to_string(),
+        header,
+        rowspan: span(&re_rowspan),
+        colspan: span(&re_colspan),
+    }
+}
+
+// "style="..." | 本文" のような属性部分を切り離す
+fn split_attributes(cell: &str) -> (Option<&str>, &str) {
+    let parts = split_top_level(cell, "|");
+    if parts.len() > 1 && !parts[0].contains("[[") && !parts[0].contains("{{") {
+        let attributes = parts[0];
+        let text = &cell[attributes.len() + 1..];
+        return (Some(attributes), text);
+    }
+    return (None, cell);
+}
+
+// セルに表示されるのはリンクの表示名なので、[[記事名|表示名]] を [[表示名]] にする。
+// 表示名の中のリンクも同じように扱う。閉じていない [[ はそのまま残す
+fn link_labels(text: &str) -> String {
+    let mut labels = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[[") {
+        labels.push_str(&rest[..start]);
+        let end = match find_closing(rest, start + 2, b'[', b']') {
+            Some(end) => end,
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        };
+        let inner = &rest[start + 2..end];
+        let label = split_top_level(inner, "|").pop().unwrap_or(inner);
+        labels.push_str("[[");
+        labels.push_str(link_labels(label).as_str());
+        labels.push_str("]]");
+        rest = &rest[end + 2..];
+    }
+    labels.push_str(rest);
+    return labels;
+}
+
+fn strip_attributes(cell: &str) -> &str {
+    split_attributes(cell).1
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter02::answer::{count_uniq_words, word_count};
+    use crate::chapter03::answer::{load_json, Article, LinkCleaner, NoneCleaner, StrongCleaner};
+    use crate::chapter03::table::{extract_tables, link_labels, Table};
+    use std::fs::create_dir_all;
+
+    const INPUT_PATH: &str = "data/jawiki-country.json.gz";
+    const KEYWORD: &str = "イギリス";
+    const TMP_PATH: &str = "data/chap03_tmp/";
+
+    fn strings(values: Vec<&str>) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    pub fn success_extract_simple_table() {
+        let article = Article::new(
+            "テスト",
+            "本文\n\
+             {| class=\"wikitable\"\n\
+             |+ 主要都市\n\
+             ! 都市 !! 人口\n\
+             |-\n\
+             | [[ロンドン]] || '''8,908,081'''\n\
+             |-\n\
+             | [[バーミンガム|バーミンガム市]]\n\
+             | 1,141,816\n\
+             |}\n\
+             後文",
+        );
+        let tables = extract_tables(
+            &article,
+            LinkCleaner {
+                chain: StrongCleaner {},
+            },
+        );
+        assert_eq!(1, tables.len());
+        assert_eq!(
+            Table {
+                caption: Some("主要都市".to_string()),
+                headers: strings(vec!["都市", "人口"]),
+                rows: vec![
+                    strings(vec!["ロンドン", "8,908,081"]),
+                    strings(vec!["バーミンガム市", "1,141,816"]),
+                ],
+            },
+            tables[0]
+        );
+    }
+
+    #[test]
+    pub fn success_link_labels() {
+        assert_eq!(
+            "[[ロンドン]]と[[市]]",
+            link_labels("[[ロンドン]]と[[バーミンガム|市]]")
+        );
+        assert_eq!(
+            "[[[[ロンドン]]の風景]]",
+            link_labels("[[ファイル:a.jpg|thumb|[[London|ロンドン]]の風景]]")
+        );
+        assert_eq!("[[a]] [[閉じない|b", link_labels("[[a]] [[閉じない|b"));
+    }
+
+    #[test]
+    pub fn success_extract_table_with_spans() {
+        let article = Article::new(
+            "テスト",
+            "{| class=\"wikitable\"\n\
+             ! rowspan=\"2\" | 国 !! colspan=2 | 人口\n\
+             |-\n\
+             ! 2001 !! 2011\n\
+             |-\n\
+             | rowspan=\"2\" style=\"text-align:left\" | イングランド || 49,138,831 || 53,012,456\n\
+             |-\n\
+             | colspan=\"2\" | 不明\n\
+             |-\n\
+             | ウェールズ\n\
+             | 2,903,085\n\
+             | 3,063,456\n\
+             |}",
+        );
+        let tables = extract_tables(&article, NoneCleaner {});
+        assert_eq!(
+            strings(vec!["国", "人口 2001", "人口 2011"]),
+            tables[0].headers
+        );
+        assert_eq!(
+            vec![
+                strings(vec!["イングランド", "49,138,831", "53,012,456"]),
+                strings(vec!["イングランド", "不明", "不明"]),
+                strings(vec!["ウェールズ", "2,903,085", "3,063,456"]),
+            ],
+            tables[0].rows
+        );
+    }
+
+    #[test]
+    pub fn success_extract_nested_and_multiline_table() {
+        let article = Article::new(
+            "テスト",
+            "{|\n\
+             | 外側\n\
+             複数行\n\
+             |\n\
+             {|\n\
+             | 内側 || {{lang|en|inner|x}}\n\
+             |}\n\
+             |}",
+        );
+        let tables = extract_tables(&article, NoneCleaner {});
+        assert_eq!(2, tables.len());
+        assert_eq!(vec![strings(vec!["外側\n複数行", ""])], tables[0].rows);
+        assert!(tables[0].headers.is_empty());
+        assert_eq!(
+            vec![strings(vec!["内側", "{{lang|en|inner|x}}"])],
+            tables[1].rows
+        );
+    }
+
+    #[test]
+    pub fn success_table_to_tsv() {
+        let table = Table {
+            caption: None,
+            headers: strings(vec!["都市", "人口"]),
+            rows: vec![
+                strings(vec!["ロンドン", "8,908,081"]),
+                strings(vec!["ロンドン", "改行\nあり\tタブ"]),
+            ],
+        };
+        assert_eq!(
+            "都市\t人口\nロンドン\t8,908,081\nロンドン\t改行 あり タブ\n",
+            table.to_tsv()
+        );
+
+        create_dir_all(TMP_PATH).expect("can't create tmp dir");
+        let output = format!("{}{}", TMP_PATH, "table.tsv");
+        table.write_tsv(output.as_str());
+        assert_eq!(3, word_count(output.as_str()));
+        assert_eq!(2, count_uniq_words(output.as_str(), 0));
+    }
+
+    #[test]
+    pub fn success_extract_tables_from_dump() {
+        let articles = load_json(INPUT_PATH, KEYWORD);
+        let tables = extract_tables(
+            &articles[0],
+            LinkCleaner {
+                chain: StrongCleaner {},
+            },
+        );
+        assert!(!tables.is_empty());
+        tables
+            .iter()
+            .for_each(|table| println!("{}", table.to_tsv()));
+    }
+}
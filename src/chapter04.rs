@@ -1 +1,10 @@
 pub mod answer;
+pub mod chart;
+pub mod co_occurrence;
+pub mod command;
+pub mod kwic;
+pub mod pattern;
+pub mod term;
+pub mod tf_idf;
+pub mod token_store;
+pub mod zipf;
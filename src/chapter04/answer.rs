@@ -1,14 +1,96 @@
+use crate::chapter04::chart::Chart;
+use crate::chapter04::co_occurrence::{write_collocates, CoOccurrence, Measure};
+use crate::chapter04::command::{
+    load_json, load_json_with_filter, load_store, load_store_with_filter, try_parse_line_json,
+    Command, SurfaceFilter,
+};
+use crate::chapter04::kwic::{query_pattern, Concordance, SortKey};
+use crate::chapter04::pattern::{PatternError, TokenPattern};
+use crate::chapter04::term::{TermKey, TokenSelector};
+use crate::chapter04::tf_idf::{Document, TfIdf};
+use crate::chapter04::token_store::{TokenRef, TokenStore, TokenStoreError, TokenStoreWriter};
+use crate::chapter04::zipf::{
+    fit_zipf, frequency_of_frequencies, rank_frequency, write_frequency_of_frequencies,
+    write_rank_frequency, ZipfFit,
+};
+use crate::sentence::split_sentences;
 use lindera::tokenizer::Tokenizer;
-use metered::{metered, ResponseTime};
+use metered::{metered, ResponseTime, Throughput};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fs::File;
+use std::io;
 use std::io::Write;
 use std::io::{BufRead, BufReader};
+use std::str::FromStr;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+
+const NEKO_TEXT_PATH: &str = "./data/chap04/neko.txt";
+const NEKO_JSON_PATH: &str = "./data/chap04/neko.txt.lindera.json";
+
+// 形態素解析のモード。lindera 0.5 には search モードがないため受け付けない
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum TokenizerMode {
+    Normal,
+    Decompose,
+}
+
+impl TokenizerMode {
+    fn as_lindera_mode(&self) -> &str {
+        match self {
+            TokenizerMode::Normal => "normal",
+            TokenizerMode::Decompose => "decompose",
+        }
+    }
+}
+
+impl Default for TokenizerMode {
+    fn default() -> Self {
+        TokenizerMode::Normal
+    }
+}
+
+impl FromStr for TokenizerMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "normal" => Ok(TokenizerMode::Normal),
+            "search" => Err("tokenizer mode search is not supported by lindera 0.5".to_string()),
+            "decompose" => Ok(TokenizerMode::Decompose),
+            _ => Err(format!("unsupported tokenizer mode. {}", s)),
+        }
+    }
+}
 
 #[derive(Default, Debug, Serialize)]
 pub struct NekoParser {
     metric_reg: NekoParserMetricRegistry,
+    mode: TokenizerMode,
+    // ユーザ辞書のCSV（表層形,品詞,読み）
+    user_dictionary: Option<String>,
+}
+
+impl NekoParser {
+    pub fn with_mode(mut self, mode: TokenizerMode) -> NekoParser {
+        self.mode = mode;
+        return self;
+    }
+
+    pub fn with_user_dictionary(mut self, path: &str) -> NekoParser {
+        self.user_dictionary = Some(path.to_string());
+        return self;
+    }
+
+    pub fn mode(&self) -> TokenizerMode {
+        self.mode
+    }
+
+    pub fn user_dictionary(&self) -> Option<&str> {
+        self.user_dictionary.as_ref().map(|path| path.as_str())
+    }
 }
 
 #[metered(registry = NekoParserMetricRegistry, /* default = self.metrics */ registry_expr = self.metric_reg)]
@@ -16,19 +98,144 @@ pub struct NekoParser {
 impl NekoParser {
     #[measure]
     pub fn load_and_parse_neko(&self) {
-        let file_path = "./data/chap04/neko.txt";
-        let file = File::open(file_path).unwrap();
-        let buf = BufReader::new(file);
-        let mut out = File::create("./data/chap04/neko.txt.lindera.json").unwrap();
+        self.parse_file(NEKO_TEXT_PATH, NEKO_JSON_PATH);
+    }
+
+    pub fn parse_file(&self, input_path: &str, output_path: &str) {
+        let mut out = create_output(output_path);
+        self.parse(open_input(input_path), &mut out);
+    }
+
+    // 1文ごとに形態素解析し、トークン列をJSONで1行ずつ書き出す。空行は出力しない
+    #[measure]
+    pub fn parse<R: BufRead, W: Write>(&self, input: R, out: &mut W) {
         let mut tokenizer = self.new_tokenizer();
-        buf.lines().filter_map(|item| item.ok()).for_each(|line| {
-            let tokens = self.tokenize(&mut tokenizer, line.as_str());
-            self.output_tokens(&tokens, &mut out);
+        let mut sentence_id = 0;
+        input.lines().filter_map(|item| item.ok()).for_each(|line| {
+            for tokens in self.tokenize_sentences(&mut tokenizer, line.as_str(), sentence_id) {
+                self.output_tokens(&tokens, out);
+                sentence_id += 1;
+            }
         });
     }
 
+    // 行を文に分割して形態素解析する。文IDは first_sentence_id からの連番
+    pub fn tokenize_sentences(
+        &self,
+        tokenizer: &mut Tokenizer,
+        line: &str,
+        first_sentence_id: usize,
+    ) -> Vec<Vec<Token>> {
+        split_sentences(line)
+            .iter()
+            .enumerate()
+            .map(|(i, sentence)| {
+                let mut tokens = self.tokenize(tokenizer, sentence.text);
+                tokens.iter_mut().for_each(|token| {
+                    token.start += sentence.start;
+                    token.end += sentence.start;
+                    token.sentence_id = first_sentence_id + i;
+                });
+                tokens
+            })
+            .collect()
+    }
+
+    // 並列化したときは、ワーカーとは別に 1 本のスレッドで入力順に書き出す
     #[measure]
-    pub fn output_tokens(&self, tokens: &Vec<Token>, buf: &mut File) {
+    pub fn parse_parallel<R: BufRead + Send, W: Write>(
+        &self,
+        input: R,
+        out: &mut W,
+        workers: usize,
+    ) {
+        let workers = workers.max(1);
+        let tokenizer = self.new_tokenizer();
+        // 読み込んでから書き出すまでの行数を window までに抑え、並べ替え待ちの行が増え続けないようにする
+        let window = workers * 64;
+        let (line_tx, line_rx) = mpsc::sync_channel::<(usize, String)>(window);
+        let line_rx = Mutex::new(line_rx);
+        let (result_tx, result_rx) = mpsc::sync_channel::<(usize, Vec<Vec<Token>>)>(window);
+        let (credit_tx, credit_rx) = mpsc::sync_channel::<()>(window);
+        for _ in 0..window {
+            credit_tx.send(()).expect("credit channel is full");
+        }
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                let line_rx = &line_rx;
+                let result_tx = result_tx.clone();
+                let mut tokenizer = tokenizer.clone();
+                scope.spawn(move || loop {
+                    let received = line_rx.lock().expect("poisoned line receiver").recv();
+                    let (index, line) = match received {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    let sentences = self.tokenize_sentences(&mut tokenizer, line.as_str(), 0);
+                    if result_tx.send((index, sentences)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(result_tx);
+            scope.spawn(move || {
+                for (index, line) in input.lines().filter_map(|item| item.ok()).enumerate() {
+                    if credit_rx.recv().is_err() || line_tx.send((index, line)).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            // 追い越して届いた行は次に書く行が揃うまで溜めておく
+            let mut pending: BTreeMap<usize, Vec<Vec<Token>>> = BTreeMap::new();
+            let mut next_index = 0;
+            let mut sentence_id = 0;
+            for (index, sentences) in result_rx {
+                pending.insert(index, sentences);
+                while let Some(sentences) = pending.remove(&next_index) {
+                    for mut tokens in sentences {
+                        tokens
+                            .iter_mut()
+                            .for_each(|token| token.sentence_id = sentence_id);
+                        self.output_tokens(&tokens, out);
+                        sentence_id += 1;
+                    }
+                    next_index += 1;
+                    // 読み込みが終わっていれば受け取り手はいない
+                    let _ = credit_tx.send(());
+                }
+            }
+        });
+    }
+
+    pub fn parse_file_parallel(&self, input_path: &str, output_path: &str, workers: usize) {
+        let mut out = create_output(output_path);
+        self.parse_parallel(open_input(input_path), &mut out, workers);
+    }
+
+    // JSONの代わりにバイナリ形式（token_store）で書き出す
+    #[measure]
+    pub fn parse_to_store<R: BufRead, W: Write>(
+        &self,
+        input: R,
+        out: &mut W,
+    ) -> Result<(), TokenStoreError> {
+        let mut tokenizer = self.new_tokenizer();
+        let mut writer = TokenStoreWriter::new();
+        for line in input.lines().filter_map(|item| item.ok()) {
+            let first_sentence_id = writer.sentence_count();
+            for tokens in self.tokenize_sentences(&mut tokenizer, line.as_str(), first_sentence_id)
+            {
+                writer.push_sentence(&tokens)?;
+            }
+        }
+        return writer.finish(out);
+    }
+
+    // impl に付けた既定の ResponseTime に加えて、書き出した文の数のスループット（文/秒）も記録する。
+    // metered は impl の既定とメソッドの指定を合わせるので、ここに ResponseTime を重ねて書くと重複になる
+    #[measure(Throughput)]
+    pub fn output_tokens<W: Write>(&self, tokens: &Vec<Token>, buf: &mut W) {
         writeln!(buf, "{}", serde_json::to_string(tokens).unwrap())
             .expect("Error during output json");
     }
@@ -36,35 +243,27 @@ impl NekoParser {
     #[measure]
     pub fn tokenize(&self, tokenizer: &mut Tokenizer, line: &str) -> Vec<Token> {
         let lindera_tokens = self.call_tokenizer(line, tokenizer);
+        let mut offset = 0;
         let tokens = lindera_tokens
             .iter()
             .map(|lindera_token| {
-                let surface = lindera_token.text.to_string();
-                let pos = lindera_token.detail[0].to_string();
-                let pos1 = if pos != "UNK" {
-                    lindera_token.detail[1].to_string()
-                } else {
-                    String::new()
-                };
-                let base = if pos != "UNK" {
-                    lindera_token.detail[6].to_string()
-                } else {
-                    String::new()
-                };
-                Token {
-                    surface,
-                    base,
-                    pos,
-                    pos1,
-                }
+                let start = offset;
+                offset += lindera_token.text.chars().count();
+                Token::from_detail(lindera_token.text, &lindera_token.detail, start, offset)
             })
             .collect();
         return tokens;
     }
 
     #[measure]
-    fn new_tokenizer(&self) -> Tokenizer {
-        lindera::tokenizer::Tokenizer::new("normal", "")
+    pub fn new_tokenizer(&self) -> Tokenizer {
+        match &self.user_dictionary {
+            Some(path) => {
+                check_user_dictionary(path).expect("invalid user dictionary");
+                Tokenizer::new_with_userdic(self.mode.as_lindera_mode(), "", path)
+            }
+            None => Tokenizer::new(self.mode.as_lindera_mode(), ""),
+        }
     }
 
     #[measure]
@@ -77,63 +276,214 @@ impl NekoParser {
     }
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+// IPADICの素性: 品詞,品詞細分類1,品詞細分類2,品詞細分類3,活用型,活用形,原形,読み,発音
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Token {
     surface: String,
     base: String,
     pos: String,
     pos1: String,
+    pos2: String,
+    pos3: String,
+    conjugation_type: String,
+    conjugation_form: String,
+    reading: String,
+    pronunciation: String,
+    // 行頭からの文字単位のオフセット [start, end)
+    start: usize,
+    end: usize,
+    is_unknown: bool,
+    sentence_id: usize,
 }
 
-trait Command {
-    fn execute(&mut self, tokens: &Vec<Token>);
-}
+impl Token {
+    fn from_detail(surface: &str, detail: &[String], start: usize, end: usize) -> Token {
+        let is_unknown = detail.is_empty() || detail[0] == "UNK";
+        let feature = |index: usize| {
+            if is_unknown {
+                String::new()
+            } else {
+                detail.get(index).cloned().unwrap_or_default()
+            }
+        };
+        Token {
+            surface: surface.to_string(),
+            base: feature(6),
+            pos: if is_unknown {
+                "UNK".to_string()
+            } else {
+                feature(0)
+            },
+            pos1: feature(1),
+            pos2: feature(2),
+            pos3: feature(3),
+            conjugation_type: feature(4),
+            conjugation_form: feature(5),
+            reading: feature(7),
+            pronunciation: feature(8),
+            start,
+            end,
+            is_unknown,
+            sentence_id: 0,
+        }
+    }
+
+    pub fn surface(&self) -> &str {
+        self.surface.as_str()
+    }
+
+    pub fn base(&self) -> &str {
+        self.base.as_str()
+    }
+
+    pub fn pos(&self) -> &str {
+        self.pos.as_str()
+    }
+
+    pub fn pos1(&self) -> &str {
+        self.pos1.as_str()
+    }
+
+    pub fn pos2(&self) -> &str {
+        self.pos2.as_str()
+    }
+
+    pub fn pos3(&self) -> &str {
+        self.pos3.as_str()
+    }
 
-trait Filter {
-    fn is_target(&self, line: &str) -> bool;
+    pub fn conjugation_type(&self) -> &str {
+        self.conjugation_type.as_str()
+    }
+
+    pub fn conjugation_form(&self) -> &str {
+        self.conjugation_form.as_str()
+    }
+
+    pub fn reading(&self) -> &str {
+        self.reading.as_str()
+    }
+
+    pub fn pronunciation(&self) -> &str {
+        self.pronunciation.as_str()
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn is_unknown(&self) -> bool {
+        self.is_unknown
+    }
+
+    pub fn sentence_id(&self) -> usize {
+        self.sentence_id
+    }
 }
 
-struct NonFilter {}
+impl<'a> From<&TokenRef<'a>> for Token {
+    fn from(token: &TokenRef<'a>) -> Token {
+        Token {
+            surface: token.surface.to_string(),
+            base: token.base.to_string(),
+            pos: token.pos.to_string(),
+            pos1: token.pos1.to_string(),
+            pos2: token.pos2.to_string(),
+            pos3: token.pos3.to_string(),
+            conjugation_type: token.conjugation_type.to_string(),
+            conjugation_form: token.conjugation_form.to_string(),
+            reading: token.reading.to_string(),
+            pronunciation: token.pronunciation.to_string(),
+            start: token.start,
+            end: token.end,
+            is_unknown: token.is_unknown,
+            sentence_id: token.sentence_id,
+        }
+    }
+}
 
-impl Filter for NonFilter {
-    fn is_target(&self, _line: &str) -> bool {
-        true
+// lindera は不正な行があるとパニックするので、事前に形式を確認する
+fn check_user_dictionary(path: &str) -> Result<(), String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("can't read user dictionary. {}: {}", path, e))?;
+    for (number, line) in content.lines().enumerate() {
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() < 3 || fields.iter().take(3).any(|field| field.trim().is_empty()) {
+            return Err(format!(
+                "{}:{}: expected \"surface,pos,reading\" but got \"{}\"",
+                path,
+                number + 1,
+                line
+            ));
+        }
     }
+    return Ok(());
 }
 
-// ch04-30. 形態素解析結果の読み込み
-fn load_json<T: Command>(cmd: &mut T) {
-    load_json_with_filter(cmd, &NonFilter {});
+fn open_input(path: &str) -> BufReader<File> {
+    BufReader::new(File::open(path).expect(format!("file not found. {}", path).as_str()))
 }
 
-fn load_json_with_filter<T: Command, U: Filter>(cmd: &mut T, filter: &U) {
-    let file = File::open("./data/chap04/neko.txt.lindera.json").unwrap();
-    let buf = BufReader::new(file);
-    buf.lines()
-        .filter_map(|item| item.ok())
-        .filter(|line| filter.is_target(line))
-        .for_each(|line| {
-            let tokens = parse_line_json(line.as_str());
-            cmd.execute(&tokens);
-        });
+fn create_output(path: &str) -> File {
+    File::create(path).expect(format!("can't create file. {}", path).as_str())
+}
+
+// JSON形式（1行1文）をバイナリ形式に変換する
+pub fn convert_json_to_store<R: BufRead, W: Write>(
+    input: R,
+    out: &mut W,
+) -> Result<(), TokenStoreError> {
+    let mut writer = TokenStoreWriter::new();
+    for (number, line) in input.lines().enumerate() {
+        let tokens =
+            try_parse_line_json(line?.as_str()).map_err(|e| TokenStoreError::InvalidJson {
+                line: number + 1,
+                message: e.to_string(),
+            })?;
+        writer.push_sentence(&tokens)?;
+    }
+    return writer.finish(out);
 }
 
-fn parse_line_json(line: &str) -> Vec<Token> {
-    return serde_json::from_str(line).unwrap();
+// バイナリ形式をJSON形式（1行1文）に変換する。TokenRef のまま書き出す
+pub fn convert_store_to_json<W: Write>(
+    store: &TokenStore,
+    out: &mut W,
+) -> Result<(), TokenStoreError> {
+    for sentence in store.sentences() {
+        writeln!(out, "{}", serde_json::to_string(&sentence?).unwrap())?;
+    }
+    return Ok(());
 }
 
 // ch04-31. 動詞
 fn extract_verb() {
-    load_json(&mut ExtractVerv {
-        out: File::create("./data/chap04/verb.txt").unwrap(),
-    });
+    extract_verb_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/verb.txt"),
+    );
+}
+
+pub fn extract_verb_from<R: BufRead, W: Write>(input: R, out: W) {
+    load_json(input, &mut ExtractVerv::new(out));
 }
 
-struct ExtractVerv {
-    out: File,
+pub struct ExtractVerv<W: Write> {
+    out: W,
 }
 
-impl Command for ExtractVerv {
+impl<W: Write> ExtractVerv<W> {
+    pub fn new(out: W) -> ExtractVerv<W> {
+        ExtractVerv { out }
+    }
+}
+
+impl<W: Write> Command for ExtractVerv<W> {
     fn execute(&mut self, tokens: &Vec<Token>) {
         tokens
             .iter()
@@ -147,16 +497,27 @@ impl Command for ExtractVerv {
 
 // ch04-32. 動詞の原形
 fn extract_verb_base() {
-    load_json(&mut ExtractVerbBase {
-        out: File::create("./data/chap04/verb_base.txt").unwrap(),
-    });
+    extract_verb_base_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/verb_base.txt"),
+    );
+}
+
+pub fn extract_verb_base_from<R: BufRead, W: Write>(input: R, out: W) {
+    load_json(input, &mut ExtractVerbBase::new(out));
 }
 
-struct ExtractVerbBase {
-    out: File,
+pub struct ExtractVerbBase<W: Write> {
+    out: W,
 }
 
-impl Command for ExtractVerbBase {
+impl<W: Write> ExtractVerbBase<W> {
+    pub fn new(out: W) -> ExtractVerbBase<W> {
+        ExtractVerbBase { out }
+    }
+}
+
+impl<W: Write> Command for ExtractVerbBase<W> {
     fn execute(&mut self, tokens: &Vec<Token>) {
         tokens
             .iter()
@@ -170,174 +531,235 @@ impl Command for ExtractVerbBase {
 
 // ch04-33. 「AのB」
 fn extract_a_and_b() {
-    load_json(&mut ExtractAandB {
-        out: File::create("./data/chap04/noun_a_and_b.txt").unwrap(),
-    })
+    extract_a_and_b_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/noun_a_and_b.txt"),
+    );
 }
 
-struct ExtractAandB {
-    out: File,
+pub fn extract_a_and_b_from<R: BufRead, W: Write>(input: R, out: W) {
+    load_json(input, &mut ExtractAandB::new(out));
 }
 
-impl Command for ExtractAandB {
+const A_AND_B_PATTERN: &str = "[pos=名詞] [surface=の] [pos=名詞]";
+
+pub struct ExtractAandB<W: Write>(PatternExtractor<W>);
+
+impl<W: Write> ExtractAandB<W> {
+    pub fn new(out: W) -> ExtractAandB<W> {
+        ExtractAandB(PatternExtractor::new(A_AND_B_PATTERN, out).unwrap())
+    }
+}
+
+impl<W: Write> Command for ExtractAandB<W> {
     fn execute(&mut self, tokens: &Vec<Token>) {
-        let mut buffer = vec![];
-        tokens.iter().for_each(|token| {
-            if token.pos == "名詞" {
-                if buffer.is_empty() {
-                    buffer.push(token.surface.to_string());
-                } else if buffer.len() == 2 {
-                    writeln!(self.out, "{}{}", buffer.join(""), token.surface)
-                        .expect("Error during writeln");
-                    println!("{}{}", buffer.join(""), token.surface);
-                } else {
-                    buffer.clear();
-                    buffer.push(token.surface.to_string());
-                }
-            } else if token.surface == "の" && buffer.len() == 1 {
-                buffer.push(token.surface.to_string());
-            }
-        });
+        self.0.execute(tokens);
+    }
+}
+
+// パターンにマッチした部分の表層形を1行ずつ書き出す
+pub struct PatternExtractor<W: Write> {
+    pattern: TokenPattern,
+    out: W,
+}
+
+impl<W: Write> PatternExtractor<W> {
+    pub fn new(pattern: &str, out: W) -> Result<PatternExtractor<W>, PatternError> {
+        Ok(PatternExtractor {
+            pattern: TokenPattern::new(pattern)?,
+            out,
+        })
+    }
+}
+
+impl<W: Write> Command for PatternExtractor<W> {
+    fn execute(&mut self, tokens: &Vec<Token>) {
+        for found in self.pattern.find_iter(tokens) {
+            let surface = found.surface(tokens);
+            writeln!(self.out, "{}", surface).expect("Error during writeln");
+            println!("{}", surface);
+        }
     }
 }
 
 // ch04-34. 名詞の連接
 fn extract_conjunction_of_nouns() {
-    let mut cmd = ExtractMaxConjunctionNoun {
-        out: File::create("./data/chap04/max_noun.txt").unwrap(),
-        buffer: vec![],
-    };
-    load_json(&mut cmd);
-    cmd.print_conjunction_nouns();
+    extract_conjunction_of_nouns_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/max_noun.txt"),
+    );
 }
 
-struct ExtractMaxConjunctionNoun {
-    out: File,
-    buffer: Vec<Vec<Token>>,
+pub fn extract_conjunction_of_nouns_from<R: BufRead, W: Write>(input: R, out: W) {
+    load_json(input, &mut ExtractMaxConjunctionNoun::new(out));
 }
 
-impl ExtractMaxConjunctionNoun {
-    fn print_conjunction_nouns(&self) {
-        self.buffer.iter().for_each(|nouns| {
-            let mut max = String::new();
-            for token in nouns.iter() {
-                max.push_str(token.surface.as_str());
-            }
-            writeln!(&self.out, "{}", max).expect("Error during writeln");
-            println!("{}", max);
-        });
+const MAX_CONJUNCTION_NOUN_PATTERN: &str = "[pos=名詞]{2,}";
+
+pub struct ExtractMaxConjunctionNoun<W: Write>(PatternExtractor<W>);
+
+impl<W: Write> ExtractMaxConjunctionNoun<W> {
+    pub fn new(out: W) -> ExtractMaxConjunctionNoun<W> {
+        ExtractMaxConjunctionNoun(PatternExtractor::new(MAX_CONJUNCTION_NOUN_PATTERN, out).unwrap())
     }
 }
 
-impl Command for ExtractMaxConjunctionNoun {
+impl<W: Write> Command for ExtractMaxConjunctionNoun<W> {
     fn execute(&mut self, tokens: &Vec<Token>) {
-        let mut nouns = vec![];
-        // TODO 参照保持でどうにかしたいけどなぁ。
-        tokens.iter().map(|token| token.clone()).for_each(|token| {
-            if token.pos == "名詞" {
-                nouns.push(token);
-            } else {
-                if nouns.len() > 1 {
-                    self.buffer.push(nouns.clone());
-                }
-                nouns = vec![]
-            }
-        });
+        self.0.execute(tokens);
     }
 }
 
 impl Token {
     fn from(token: &Token) -> Token {
-        Token {
-            surface: token.surface.to_string(),
-            base: token.base.to_string(),
-            pos: token.pos.to_string(),
-            pos1: token.pos1.to_string(),
-        }
+        token.clone()
     }
 }
 
 // ch04-35. 単語の出現頻度
 fn count_token_frequency() {
-    let mut cmd = TokenCounter {
-        out: File::create("./data/chap04/token_freq.txt").unwrap(),
-        terms_count: BTreeMap::new(),
-    };
-    load_json(&mut cmd);
+    count_token_frequency_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/token_freq.txt"),
+    );
+}
+
+pub fn count_token_frequency_from<R: BufRead, W: Write>(input: R, out: W) {
+    let mut cmd = TokenCounter::new(out);
+    load_json(input, &mut cmd);
+    cmd.print();
+}
+
+pub fn count_token_frequency_from_store<W: Write>(store: &TokenStore, out: W) {
+    let mut cmd = TokenCounter::new(out);
+    load_store(store, &mut cmd);
     cmd.print();
 }
 
-struct TokenCounter {
-    out: File,
+pub struct TokenCounter<W: Write> {
+    out: W,
+    key: TermKey,
+    selector: TokenSelector,
     terms_count: BTreeMap<String, u32>,
 }
 
-impl TokenCounter {
-    fn print(&self) {
+impl<W: Write> TokenCounter<W> {
+    pub fn new(out: W) -> TokenCounter<W> {
+        TokenCounter {
+            out,
+            key: TermKey::default(),
+            selector: TokenSelector::default(),
+            terms_count: BTreeMap::new(),
+        }
+    }
+
+    // 表層形、原形、「原形/品詞」、品詞のどれで数えるか
+    pub fn with_key(mut self, key: TermKey) -> TokenCounter<W> {
+        self.key = key;
+        return self;
+    }
+
+    pub fn with_selector(mut self, selector: TokenSelector) -> TokenCounter<W> {
+        self.selector = selector;
+        return self;
+    }
+
+    pub fn counts(&self) -> &BTreeMap<String, u32> {
+        &self.terms_count
+    }
+
+    pub fn print(&mut self) {
         for (key, value) in &self.terms_count {
-            writeln!(&self.out, "{}, {}", key, value).expect("Error during writeln");
+            writeln!(self.out, "{}, {}", key, value).expect("Error during writeln");
             println!("{}, {}", key, value);
         }
     }
 
-    fn print_top10(&mut self) {
-        let mut key_values: Vec<(&String, &u32)> =
-            self.terms_count.iter().collect::<Vec<(&String, &u32)>>();
-        key_values.sort_by(|x, y| y.1.cmp(&x.1));
-        key_values.iter().take(10).for_each(|(key, value)| {
-            writeln!(&self.out, "{}, {}", key, value).expect("Error during writeln");
+    // 頻度の降順に n 語。同じ頻度なら語の順
+    pub fn top(&self, n: usize) -> Vec<(&str, u32)> {
+        top_terms(&self.terms_count, n)
+    }
+
+    pub fn print_top10(&mut self) {
+        let key_values = top_terms(&self.terms_count, 10);
+        let out = &mut self.out;
+        key_values.iter().for_each(|(key, value)| {
+            writeln!(out, "{}, {}", key, value).expect("Error during writeln");
             println!("{}, {}", key, value);
         });
     }
 }
 
-impl Command for TokenCounter {
+impl<W: Write> Command for TokenCounter<W> {
     fn execute(&mut self, tokens: &Vec<Token>) {
-        tokens.iter().for_each(|token| {
-            let value = self.terms_count.get(token.surface.as_str());
-            let count = match value {
-                None => 1,
-                Some(counter) => counter + 1,
-            };
-            self.terms_count.insert(token.surface.to_string(), count);
-        });
+        for token in tokens {
+            if self.selector.is_target(token) {
+                *self.terms_count.entry(self.key.key(token)).or_insert(0) += 1;
+            }
+        }
     }
 }
 
 // ch04-36. 頻度上位10語
 fn count_token_frequency_top10() {
-    let mut cmd = TokenCounter {
-        out: File::create("./data/chap04/token_freq_top10.txt").unwrap(),
-        terms_count: BTreeMap::new(),
-    };
-    load_json(&mut cmd);
+    count_token_frequency_top10_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/token_freq_top10.txt"),
+    );
+}
+
+pub fn count_token_frequency_top10_from<R: BufRead, W: Write>(input: R, out: W) {
+    let mut cmd = TokenCounter::new(out);
+    load_json(input, &mut cmd);
     cmd.print_top10();
 }
 
-// ch04-37. 「猫」と共起頻度の高い上位10語
-fn count_co_occurrence_cat_top10() {
-    let mut cmd = CoOccurrenceCat {
-        out: File::create("./data/chap04/co_occurrence_cat_top10.txt").unwrap(),
-        co_occurrence_term: BTreeMap::new(),
-    };
-    load_json_with_filter(&mut cmd, &CatFilter {});
+// 内容語を「原形/品詞」で数えた上位10語
+fn count_base_pos_frequency_top10() {
+    let mut cmd = TokenCounter::new(create_output("./data/chap04/base_pos_freq_top10.txt"))
+        .with_key(TermKey::BasePos)
+        .with_selector(TokenSelector::content_words());
+    load_json(open_input(NEKO_JSON_PATH), &mut cmd);
     cmd.print_top10();
 }
 
-struct CatFilter {}
+// 章ごとに TF-IDF の高い10語
+fn tf_idf_by_chapter() {
+    tf_idf_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/tf_idf_chapter_top10.tsv"),
+        TfIdf::new(Document::Chapter)
+            .with_key(TermKey::Base)
+            .with_selector(TokenSelector::content_words()),
+        10,
+    );
+}
 
-impl Filter for CatFilter {
-    fn is_target(&self, line: &str) -> bool {
-        line.contains("猫")
-    }
+pub fn tf_idf_from<R: BufRead, W: Write>(input: R, mut out: W, mut cmd: TfIdf, n: usize) {
+    load_json(input, &mut cmd);
+    cmd.write_top(&mut out, n).expect("Error during writeln");
 }
 
-struct CoOccurrenceCat {
-    out: File,
+// ch04-37. 「猫」と共起頻度の高い上位10語
+fn count_co_occurrence_cat_top10() {
+    count_co_occurrence_cat_top10_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/co_occurrence_cat_top10.txt"),
+    );
+}
+
+pub fn count_co_occurrence_cat_top10_from<R: BufRead, W: Write>(input: R, out: W) {
+    let mut cmd = CoOccurrenceCat::new(out);
+    load_json_with_filter(input, &mut cmd, &SurfaceFilter::new("猫"));
+    cmd.print_top10();
+}
+
+pub struct CoOccurrenceCat<W: Write> {
+    out: W,
     co_occurrence_term: BTreeMap<String, u32>,
 }
 
-impl Command for CoOccurrenceCat {
+impl<W: Write> Command for CoOccurrenceCat<W> {
     fn execute(&mut self, tokens: &Vec<Token>) {
         tokens
             .iter()
@@ -354,49 +776,265 @@ impl Command for CoOccurrenceCat {
     }
 }
 
-impl CoOccurrenceCat {
-    fn print(&self) {
+impl<W: Write> CoOccurrenceCat<W> {
+    pub fn new(out: W) -> CoOccurrenceCat<W> {
+        CoOccurrenceCat {
+            out,
+            co_occurrence_term: BTreeMap::new(),
+        }
+    }
+
+    pub fn print(&mut self) {
         for (key, value) in &self.co_occurrence_term {
-            writeln!(&self.out, "{}, {}", key, value).expect("Error during writeln");
+            writeln!(self.out, "{}, {}", key, value).expect("Error during writeln");
             println!("{}, {}", key, value);
         }
     }
 
-    fn print_top10(self) {
-        let mut key_values: Vec<(&String, &u32)> = self
-            .co_occurrence_term
-            .iter()
-            .collect::<Vec<(&String, &u32)>>();
-        key_values.sort_by(|x, y| y.1.cmp(&x.1));
-        key_values.iter().take(10).for_each(|(key, value)| {
-            writeln!(&self.out, "{}, {}", key, value).expect("Error during writeln");
+    pub fn counts(&self) -> &BTreeMap<String, u32> {
+        &self.co_occurrence_term
+    }
+
+    pub fn top(&self, n: usize) -> Vec<(&str, u32)> {
+        top_terms(&self.co_occurrence_term, n)
+    }
+
+    pub fn print_top10(mut self) {
+        let key_values = top_terms(&self.co_occurrence_term, 10);
+        let out = &mut self.out;
+        key_values.iter().for_each(|(key, value)| {
+            writeln!(out, "{}, {}", key, value).expect("Error during writeln");
             println!("{}, {}", key, value);
         });
     }
 }
 
+fn top_terms(counts: &BTreeMap<String, u32>, n: usize) -> Vec<(&str, u32)> {
+    let mut key_values: Vec<(&str, u32)> = counts
+        .iter()
+        .map(|(key, value)| (key.as_str(), *value))
+        .collect();
+    key_values.sort_by(|x, y| y.1.cmp(&x.1).then_with(|| x.0.cmp(y.0)));
+    key_values.truncate(n);
+    return key_values;
+}
+
 // ch04-38. ヒストグラム
 fn count_co_occurrence_cat() {
-    let mut cmd = CoOccurrenceCat {
-        out: File::create("./data/chap04/co_occurrence_cat.txt").unwrap(),
-        co_occurrence_term: BTreeMap::new(),
-    };
-    load_json_with_filter(&mut cmd, &CatFilter {});
+    count_co_occurrence_cat_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/co_occurrence_cat.txt"),
+    );
+}
+
+pub fn count_co_occurrence_cat_from<R: BufRead, W: Write>(input: R, out: W) {
+    let mut cmd = CoOccurrenceCat::new(out);
+    load_json_with_filter(input, &mut cmd, &SurfaceFilter::new("猫"));
     cmd.print();
 }
 
+pub fn count_co_occurrence_cat_from_store<W: Write>(store: &TokenStore, out: W) {
+    let mut cmd = CoOccurrenceCat::new(out);
+    load_store_with_filter(store, &mut cmd, &SurfaceFilter::new("猫"));
+    cmd.print();
+}
+
+// 「猫」と結びつきの強い語（原形、内容語のみ、PPMI の順）
+fn co_occurrence_cat_collocates() {
+    collocates_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/co_occurrence_cat_ppmi.tsv"),
+        CoOccurrence::new("猫")
+            .with_key(TermKey::Base)
+            .with_selector(TokenSelector::content_words()),
+        Measure::Ppmi,
+        2,
+    );
+}
+
+pub fn collocates_from<R: BufRead, W: Write>(
+    input: R,
+    mut out: W,
+    mut cmd: CoOccurrence,
+    measure: Measure,
+    min_frequency: u64,
+) {
+    load_json(input, &mut cmd);
+    write_collocates(&mut out, &cmd.collocates(measure, min_frequency))
+        .expect("Error during writeln");
+}
+
+// 「猫」の前後5語を直後の語の順に並べる
+fn kwic_cat() {
+    kwic_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/kwic_cat.tsv"),
+        Concordance::new(query_pattern("猫").expect("invalid query")),
+        SortKey::Right,
+    );
+}
+
+pub fn kwic_from<R: BufRead, W: Write>(
+    input: R,
+    mut out: W,
+    mut concordance: Concordance,
+    sort: SortKey,
+) {
+    load_json(input, &mut concordance);
+    concordance.sort(sort);
+    concordance
+        .write_tsv(&mut out)
+        .expect("Error during writeln");
+}
+
 // ch04-39. Zipfの法則
+fn zipf_law() -> Option<ZipfFit> {
+    zipf_law_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/zipf_rank_frequency.tsv"),
+        create_output("./data/chap04/zipf_frequency_of_frequencies.tsv"),
+    )
+}
+
+// 順位と頻度、頻度ごとの語の数をプロット用のTSVに書き出し、両対数で当てはめた結果を返す
+pub fn zipf_law_from<R: BufRead, W: Write, H: Write>(
+    input: R,
+    mut rank_out: W,
+    mut histogram_out: H,
+) -> Option<ZipfFit> {
+    let mut cmd = TokenCounter::new(io::sink());
+    load_json(input, &mut cmd);
+    let ranking = rank_frequency(cmd.counts());
+    let fit = fit_zipf(&ranking, 1, ranking.len());
+    write_rank_frequency(&mut rank_out, &ranking, fit.as_ref()).expect("Error during writeln");
+    write_frequency_of_frequencies(&mut histogram_out, &frequency_of_frequencies(&ranking))
+        .expect("Error during writeln");
+    if let Some(fit) = &fit {
+        println!(
+            "exponent: {:.4}, intercept: {:.4}, R^2: {:.4}, RMSE: {:.4}, points: {}",
+            fit.exponent, fit.intercept, fit.r_squared, fit.rmse, fit.points
+        );
+    }
+    return fit;
+}
+
+// ch04-36..39 のグラフを SVG で書き出す
+fn plot_charts() {
+    plot_token_frequency_top10_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/token_freq_top10.svg"),
+    );
+    plot_co_occurrence_cat_top10_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/co_occurrence_cat_top10.svg"),
+    );
+    plot_token_frequency_histogram_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/token_freq_histogram.svg"),
+    );
+    plot_zipf_from(
+        open_input(NEKO_JSON_PATH),
+        create_output("./data/chap04/zipf.svg"),
+    );
+}
+
+const HISTOGRAM_BINS: usize = 20;
+
+pub fn plot_token_frequency_top10_from<R: BufRead, W: Write>(input: R, mut out: W) {
+    let mut cmd = TokenCounter::new(io::sink());
+    load_json(input, &mut cmd);
+    let items: Vec<(&str, f64)> = cmd
+        .top(10)
+        .into_iter()
+        .map(|(key, value)| (key, value as f64))
+        .collect();
+    let svg = Chart::new("出現頻度の高い10語")
+        .with_labels("単語", "出現頻度")
+        .bar(&items);
+    write!(out, "{}", svg).expect("Error during write");
+}
+
+pub fn plot_co_occurrence_cat_top10_from<R: BufRead, W: Write>(input: R, mut out: W) {
+    let mut cmd = CoOccurrenceCat::new(io::sink());
+    load_json_with_filter(input, &mut cmd, &SurfaceFilter::new("猫"));
+    let items: Vec<(&str, f64)> = cmd
+        .top(10)
+        .into_iter()
+        .map(|(key, value)| (key, value as f64))
+        .collect();
+    let svg = Chart::new("「猫」と共起頻度の高い10語")
+        .with_labels("単語", "共起頻度")
+        .bar(&items);
+    write!(out, "{}", svg).expect("Error during write");
+}
+
+// 横軸に出現頻度、縦軸にその頻度をとる単語の種類数
+pub fn plot_token_frequency_histogram_from<R: BufRead, W: Write>(input: R, mut out: W) {
+    let mut cmd = TokenCounter::new(io::sink());
+    load_json(input, &mut cmd);
+    let values: Vec<f64> = cmd.counts().values().map(|value| *value as f64).collect();
+    let svg = Chart::new("単語の出現頻度のヒストグラム")
+        .with_labels("出現頻度", "単語の種類数")
+        .histogram(&values, HISTOGRAM_BINS);
+    write!(out, "{}", svg).expect("Error during write");
+}
+
+// 両対数の順位・頻度と、当てはめた直線
+pub fn plot_zipf_from<R: BufRead, W: Write>(input: R, mut out: W) -> Option<ZipfFit> {
+    let mut cmd = TokenCounter::new(io::sink());
+    load_json(input, &mut cmd);
+    let ranking = rank_frequency(cmd.counts());
+    let fit = fit_zipf(&ranking, 1, ranking.len());
+    let points: Vec<(f64, f64)> = ranking
+        .iter()
+        .map(|term| (term.rank as f64, term.frequency as f64))
+        .collect();
+    let svg = Chart::new("Zipfの法則")
+        .with_labels("出現頻度順位", "出現頻度")
+        .log_log(
+            &points,
+            fit.as_ref().map(|fit| (fit.exponent, fit.intercept)),
+        );
+    write!(out, "{}", svg).expect("Error during write");
+    return fit;
+}
 
 #[cfg(test)]
 mod tests {
     use crate::chapter04::answer::{
-        count_co_occurrence_cat, count_co_occurrence_cat_top10, count_token_frequency,
-        count_token_frequency_top10, extract_a_and_b, extract_conjunction_of_nouns, extract_verb,
-        extract_verb_base, NekoParser,
+        check_user_dictionary, co_occurrence_cat_collocates, collocates_from,
+        convert_json_to_store, convert_store_to_json, count_base_pos_frequency_top10,
+        count_co_occurrence_cat, count_co_occurrence_cat_from, count_co_occurrence_cat_from_store,
+        count_co_occurrence_cat_top10, count_token_frequency, count_token_frequency_from,
+        count_token_frequency_from_store, count_token_frequency_top10, extract_a_and_b,
+        extract_a_and_b_from, extract_conjunction_of_nouns, extract_conjunction_of_nouns_from,
+        extract_verb, extract_verb_base, extract_verb_from, kwic_cat, kwic_from, plot_charts,
+        plot_co_occurrence_cat_top10_from, plot_token_frequency_histogram_from,
+        plot_token_frequency_top10_from, plot_zipf_from, tf_idf_by_chapter, tf_idf_from, zipf_law,
+        zipf_law_from, NekoParser, Token, TokenCounter, TokenizerMode,
     };
+    use crate::chapter04::co_occurrence::{CoOccurrence, Context, Measure};
+    use crate::chapter04::command::load_json;
+    use crate::chapter04::kwic::{query_pattern, Concordance, SortKey};
+    use crate::chapter04::term::{TermKey, TokenSelector};
+    use crate::chapter04::tf_idf::{Document, TfIdf};
+    use crate::chapter04::token_store::{TokenStore, TokenStoreError};
     use lindera::tokenizer::Tokenizer;
+    use std::fs;
+    use std::io::Cursor;
     use std::path::Path;
 
+    const TOKENS_JSON: &str = "[{\"surface\":\"猫\",\"base\":\"猫\",\"pos\":\"名詞\",\"pos1\":\"一般\"},\
+                               {\"surface\":\"の\",\"base\":\"の\",\"pos\":\"助詞\",\"pos1\":\"連体化\"},\
+                               {\"surface\":\"額\",\"base\":\"額\",\"pos\":\"名詞\",\"pos1\":\"一般\"},\
+                               {\"surface\":\"を\",\"base\":\"を\",\"pos\":\"助詞\",\"pos1\":\"格助詞\"},\
+                               {\"surface\":\"見\",\"base\":\"見る\",\"pos\":\"動詞\",\"pos1\":\"自立\"},\
+                               {\"surface\":\"た\",\"base\":\"た\",\"pos\":\"助動詞\",\"pos1\":\"\"}]\n\
+                               [{\"surface\":\"人間\",\"base\":\"人間\",\"pos\":\"名詞\",\"pos1\":\"一般\"},\
+                               {\"surface\":\"社会\",\"base\":\"社会\",\"pos\":\"名詞\",\"pos1\":\"一般\"},\
+                               {\"surface\":\"を\",\"base\":\"を\",\"pos\":\"助詞\",\"pos1\":\"格助詞\"},\
+                               {\"surface\":\"見\",\"base\":\"見る\",\"pos\":\"動詞\",\"pos1\":\"自立\"}]\n";
+
     #[test]
     fn success_tokenize() {
         let text = "関西国際空港";
@@ -409,9 +1047,115 @@ mod tests {
             assert_eq!(token.base, "関西国際空港");
             assert_eq!(token.pos, "名詞");
             assert_eq!(token.pos1, "固有名詞");
+            assert_eq!(token.pos2, "組織");
+            assert_eq!(token.reading, "カンサイコクサイクウコウ");
+            assert_eq!(token.start, 0);
+            assert_eq!(token.end, 6);
+            assert!(!token.is_unknown);
         }
     }
 
+    #[test]
+    fn success_tokenizer_mode() {
+        assert_eq!(Ok(TokenizerMode::Normal), "normal".parse());
+        assert_eq!(
+            Err("tokenizer mode search is not supported by lindera 0.5".to_string()),
+            "search".parse::<TokenizerMode>()
+        );
+        assert_eq!(Ok(TokenizerMode::Decompose), "decompose".parse());
+        assert!("extended".parse::<TokenizerMode>().is_err());
+        assert_eq!(TokenizerMode::Normal, NekoParser::default().mode());
+    }
+
+    #[test]
+    fn success_tokenize_with_user_dictionary() {
+        let path = "./data/chap04/user_dictionary_test.csv";
+        fs::write(
+            path,
+            "東京スカイツリー,カスタム名詞,トウキョウスカイツリー\n",
+        )
+        .unwrap();
+        let parser = NekoParser::default()
+            .with_mode(TokenizerMode::Normal)
+            .with_user_dictionary(path);
+        assert_eq!(Some(path), parser.user_dictionary());
+        let mut tokenizer = parser.new_tokenizer();
+        let tokens = parser.tokenize(&mut tokenizer, "東京スカイツリーの最寄り駅");
+        assert_eq!("東京スカイツリー", tokens[0].surface());
+        assert_eq!("カスタム名詞", tokens[0].pos());
+        assert_eq!("トウキョウスカイツリー", tokens[0].reading());
+    }
+
+    #[test]
+    fn success_check_user_dictionary() {
+        let path = "./data/chap04/user_dictionary_invalid.csv";
+        fs::write(
+            path,
+            "東京スカイツリー,カスタム名詞,トウキョウスカイツリー\n\nネコ,名詞\n",
+        )
+        .unwrap();
+        let error = check_user_dictionary(path).unwrap_err();
+        assert!(error.ends_with(":2: expected \"surface,pos,reading\" but got \"\""));
+        assert!(check_user_dictionary("./data/chap04/not_found.csv").is_err());
+    }
+
+    #[test]
+    fn success_token_from_detail() {
+        let detail: Vec<String> = vec![
+            "動詞",
+            "自立",
+            "*",
+            "*",
+            "一段",
+            "連用形",
+            "見る",
+            "ミ",
+            "ミ",
+        ]
+        .iter()
+        .map(|feature| feature.to_string())
+        .collect();
+        let token = Token::from_detail("見", &detail, 3, 4);
+        assert_eq!("見る", token.base());
+        assert_eq!("動詞", token.pos());
+        assert_eq!("*", token.pos2());
+        assert_eq!("一段", token.conjugation_type());
+        assert_eq!("連用形", token.conjugation_form());
+        assert_eq!("ミ", token.reading());
+        assert_eq!("ミ", token.pronunciation());
+        assert_eq!((3, 4), (token.start(), token.end()));
+        assert!(!token.is_unknown());
+
+        let unknown = Token::from_detail("ｗ", &vec!["UNK".to_string()], 0, 1);
+        assert_eq!("UNK", unknown.pos());
+        assert_eq!("", unknown.base());
+        assert!(unknown.is_unknown());
+    }
+
+    #[test]
+    fn success_token_json_round_trip() {
+        let parser = NekoParser::default();
+        let mut tokenizer = Tokenizer::new("normal", "");
+        let tokens = parser.tokenize(&mut tokenizer, "吾輩は猫である");
+        let json = serde_json::to_string(&tokens).unwrap();
+        let restored: Vec<Token> = serde_json::from_str(json.as_str()).unwrap();
+        assert_eq!(tokens, restored);
+        let offsets: Vec<(usize, usize)> = restored.iter().map(|t| (t.start, t.end)).collect();
+        assert_eq!(0, offsets[0].0);
+        assert_eq!(7, offsets[offsets.len() - 1].1);
+        offsets
+            .windows(2)
+            .for_each(|pair| assert_eq!(pair[0].1, pair[1].0));
+
+        // 旧形式のJSONも読み込める
+        let old: Vec<Token> = serde_json::from_str(
+            "[{\"surface\":\"猫\",\"base\":\"猫\",\"pos\":\"名詞\",\"pos1\":\"一般\"}]",
+        )
+        .unwrap();
+        assert_eq!("", old[0].reading());
+        assert!(!old[0].is_unknown());
+    }
+
     #[test]
     fn success_output_tokenlists() {
         let parser = NekoParser::default();
@@ -421,6 +1165,147 @@ mod tests {
         assert!(Path::new("./data/chap04/neko.txt.lindera.json").exists());
     }
 
+    #[test]
+    fn success_parse_from_reader() {
+        let parser = NekoParser::default();
+        let mut out: Vec<u8> = vec![];
+        parser.parse(
+            Cursor::new("　吾輩は猫である。名前はまだ無い。\n\n「どうした。」と云う。\n"),
+            &mut out,
+        );
+        let json = String::from_utf8(out).unwrap();
+        let sentences: Vec<Vec<Token>> = json
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(3, sentences.len());
+        sentences.iter().enumerate().for_each(|(id, tokens)| {
+            assert!(tokens.iter().all(|token| token.sentence_id() == id));
+        });
+        assert_eq!(1, sentences[0][0].start());
+        assert_eq!(9, sentences[1][0].start());
+        assert_eq!(0, sentences[2][0].start());
+        let second: String = sentences[1].iter().map(|token| token.surface()).collect();
+        assert_eq!("名前はまだ無い。", second);
+    }
+
+    #[test]
+    fn success_parse_parallel_keeps_order() {
+        let text: String = (0..200)
+            .map(|i| format!("{}番目の猫である。名前は{}。\n\n", i, i))
+            .collect();
+        let parser = NekoParser::default();
+        let mut sequential: Vec<u8> = vec![];
+        parser.parse(Cursor::new(text.as_str()), &mut sequential);
+        let mut parallel: Vec<u8> = vec![];
+        parser.parse_parallel(Cursor::new(text.as_str()), &mut parallel, 4);
+        assert_eq!(
+            400,
+            String::from_utf8(parallel.clone()).unwrap().lines().count()
+        );
+        assert_eq!(sequential, parallel);
+
+        let metrics = serde_json::to_value(&parser).unwrap();
+        assert!(metrics["metric_reg"]["output_tokens"]["throughput"].is_object());
+        assert!(metrics["metric_reg"]["output_tokens"]["response_time"].is_object());
+
+        // 読み込みから書き出しまでの行数の上限（workers * 64）を超える入力でも止まらない
+        let mut single: Vec<u8> = vec![];
+        parser.parse_parallel(Cursor::new(text.as_str()), &mut single, 1);
+        assert_eq!(sequential, single);
+    }
+
+    #[test]
+    fn success_token_store_round_trip() {
+        let mut store_bytes: Vec<u8> = vec![];
+        convert_json_to_store(Cursor::new(TOKENS_JSON), &mut store_bytes).unwrap();
+        assert!(store_bytes.len() < TOKENS_JSON.len() / 2);
+        let store = TokenStore::open(&store_bytes).unwrap();
+        assert_eq!(2, store.sentence_count());
+        assert_eq!("見る", store.sentence(1).unwrap()[3].base);
+
+        let mut json: Vec<u8> = vec![];
+        convert_store_to_json(&store, &mut json).unwrap();
+        let restored: Vec<Vec<Token>> = String::from_utf8(json)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let original: Vec<Vec<Token>> = TOKENS_JSON
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(original, restored);
+
+        let mut from_json: Vec<u8> = vec![];
+        count_co_occurrence_cat_from(Cursor::new(TOKENS_JSON), &mut from_json);
+        let mut from_store: Vec<u8> = vec![];
+        count_co_occurrence_cat_from_store(&store, &mut from_store);
+        assert_eq!(from_json, from_store);
+        let mut freq: Vec<u8> = vec![];
+        count_token_frequency_from_store(&store, &mut freq);
+        assert!(String::from_utf8(freq).unwrap().contains("を, 2\n"));
+
+        match convert_json_to_store(Cursor::new("[]\n[{\"surface\":"), &mut vec![]) {
+            Err(TokenStoreError::InvalidJson { line: 2, .. }) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        let reversed = "[{\"surface\":\"猫\",\"start\":3,\"end\":1}]\n";
+        match convert_json_to_store(Cursor::new(reversed), &mut vec![]) {
+            Err(TokenStoreError::InvalidSpan { start: 3, end: 1 }) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn success_parse_to_store() {
+        let parser = NekoParser::default();
+        let text = "　吾輩は猫である。名前はまだ無い。\n\nどこで生れたかとんと見当がつかぬ。\n";
+        let mut json: Vec<u8> = vec![];
+        parser.parse(Cursor::new(text), &mut json);
+        let mut store_bytes: Vec<u8> = vec![];
+        parser
+            .parse_to_store(Cursor::new(text), &mut store_bytes)
+            .unwrap();
+        let store = TokenStore::open(&store_bytes).unwrap();
+        assert_eq!(3, store.sentence_count());
+        let mut converted: Vec<u8> = vec![];
+        convert_store_to_json(&store, &mut converted).unwrap();
+        assert_eq!(json, converted);
+    }
+
+    #[test]
+    fn success_extract_a_and_b_resets_after_match() {
+        let json = "[{\"surface\":\"猫\",\"pos\":\"名詞\"},{\"surface\":\"の\",\"pos\":\"助詞\"},\
+                    {\"surface\":\"額\",\"pos\":\"名詞\"},{\"surface\":\"と\",\"pos\":\"助詞\"},\
+                    {\"surface\":\"犬\",\"pos\":\"名詞\"},{\"surface\":\"の\",\"pos\":\"助詞\"},\
+                    {\"surface\":\"鼻\",\"pos\":\"名詞\"}]\n";
+        let mut out: Vec<u8> = vec![];
+        extract_a_and_b_from(Cursor::new(json), &mut out);
+        assert_eq!("猫の額\n犬の鼻\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn success_commands_with_reader_and_writer() {
+        let mut verbs: Vec<u8> = vec![];
+        extract_verb_from(Cursor::new(TOKENS_JSON), &mut verbs);
+        assert_eq!("見\n見\n", String::from_utf8(verbs).unwrap());
+
+        let mut a_and_b: Vec<u8> = vec![];
+        extract_a_and_b_from(Cursor::new(TOKENS_JSON), &mut a_and_b);
+        assert_eq!("猫の額\n", String::from_utf8(a_and_b).unwrap());
+
+        let mut nouns: Vec<u8> = vec![];
+        extract_conjunction_of_nouns_from(Cursor::new(TOKENS_JSON), &mut nouns);
+        assert_eq!("人間社会\n", String::from_utf8(nouns).unwrap());
+
+        let mut freq: Vec<u8> = vec![];
+        count_token_frequency_from(Cursor::new(TOKENS_JSON), &mut freq);
+        let freq = String::from_utf8(freq).unwrap();
+        assert!(freq.contains("を, 2\n"));
+        assert!(freq.contains("見, 2\n"));
+    }
+
     #[test]
     fn success_output_verv() {
         extract_verb();
@@ -460,4 +1345,158 @@ mod tests {
     fn success_output_co_occurrence_cat() {
         count_co_occurrence_cat();
     }
+
+    #[test]
+    fn success_zipf_law_from_reader() {
+        let mut ranks: Vec<u8> = vec![];
+        let mut histogram: Vec<u8> = vec![];
+        let fit = zipf_law_from(Cursor::new(TOKENS_JSON), &mut ranks, &mut histogram).unwrap();
+        assert!(fit.exponent > 0.0);
+        let ranks = String::from_utf8(ranks).unwrap();
+        assert!(ranks.lines().nth(1).unwrap().starts_with("1\tを\t2\t"));
+        assert_eq!(
+            "frequency\tterms\n1\t6\n2\t2\n",
+            String::from_utf8(histogram).unwrap()
+        );
+    }
+
+    #[test]
+    fn success_output_zipf_law() {
+        let fit = zipf_law().expect("not enough terms");
+        assert!(fit.exponent > 0.0);
+        assert!(fit.r_squared > 0.8);
+        assert!(Path::new("./data/chap04/zipf_rank_frequency.tsv").exists());
+    }
+
+    #[test]
+    fn success_token_counter_top() {
+        let mut cmd = TokenCounter::new(Vec::new());
+        load_json(Cursor::new(TOKENS_JSON), &mut cmd);
+        // 同じ頻度なら語の順
+        assert_eq!(vec![("を", 2), ("見", 2), ("た", 1)], cmd.top(3));
+        cmd.print_top10();
+        let out = String::from_utf8(cmd.out).unwrap();
+        assert!(out.starts_with("を, 2\n見, 2\nた, 1\n"));
+    }
+
+    #[test]
+    fn success_plot_from_reader() {
+        let mut out: Vec<u8> = vec![];
+        plot_token_frequency_top10_from(Cursor::new(TOKENS_JSON), &mut out);
+        let svg = String::from_utf8(out).unwrap();
+        assert!(svg.contains("出現頻度の高い10語"));
+        assert!(svg.contains("<title>を: 2</title>"));
+        assert_eq!(8, svg.matches("<rect x=").count());
+
+        let mut out: Vec<u8> = vec![];
+        plot_co_occurrence_cat_top10_from(Cursor::new(TOKENS_JSON), &mut out);
+        let svg = String::from_utf8(out).unwrap();
+        assert!(svg.contains("<title>額: 1</title>"));
+        assert!(!svg.contains("<title>猫:"));
+        assert!(!svg.contains("人間"));
+
+        let mut out: Vec<u8> = vec![];
+        plot_token_frequency_histogram_from(Cursor::new(TOKENS_JSON), &mut out);
+        assert!(String::from_utf8(out).unwrap().contains("単語の種類数"));
+
+        let mut out: Vec<u8> = vec![];
+        let fit = plot_zipf_from(Cursor::new(TOKENS_JSON), &mut out).unwrap();
+        assert!(fit.exponent > 0.0);
+        assert_eq!(
+            8,
+            String::from_utf8(out).unwrap().matches("<circle").count()
+        );
+    }
+
+    #[test]
+    fn success_output_charts() {
+        plot_charts();
+        assert!(Path::new("./data/chap04/zipf.svg").exists());
+    }
+
+    #[test]
+    fn success_collocates_from_reader() {
+        let mut out: Vec<u8> = vec![];
+        collocates_from(
+            Cursor::new(TOKENS_JSON),
+            &mut out,
+            CoOccurrence::new("見る")
+                .with_key(TermKey::Base)
+                .with_context(Context::Window(1))
+                .with_selector(TokenSelector::content_words()),
+            Measure::Frequency,
+            1,
+        );
+        assert_eq!(
+            "term\tfrequency\tterm_frequency\tscore\n社会\t1\t1\t1.0000\n額\t1\t1\t1.0000\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn success_output_co_occurrence_cat_collocates() {
+        co_occurrence_cat_collocates();
+        assert!(Path::new("./data/chap04/co_occurrence_cat_ppmi.tsv").exists());
+    }
+
+    #[test]
+    fn success_token_counter_keys() {
+        let mut cmd = TokenCounter::new(Vec::new())
+            .with_key(TermKey::BasePos)
+            .with_selector(TokenSelector::content_words().with_stopwords(&["人間"]));
+        load_json(Cursor::new(TOKENS_JSON), &mut cmd);
+        assert_eq!(vec![("見る/動詞", 2), ("猫/名詞", 1)], cmd.top(2));
+        assert!(cmd.counts().get("人間/名詞").is_none());
+
+        let mut cmd = TokenCounter::new(Vec::new()).with_key(TermKey::Pos);
+        load_json(Cursor::new(TOKENS_JSON), &mut cmd);
+        assert_eq!(
+            vec![("名詞", 4), ("助詞", 3), ("動詞", 2), ("助動詞", 1)],
+            cmd.top(10)
+        );
+    }
+
+    #[test]
+    fn success_tf_idf_from_reader() {
+        let mut out: Vec<u8> = vec![];
+        tf_idf_from(
+            Cursor::new(TOKENS_JSON),
+            &mut out,
+            TfIdf::new(Document::Line).with_key(TermKey::Base),
+            1,
+        );
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(3, lines.len());
+        assert!(lines[1].starts_with("1\tた\t"));
+        assert!(lines[2].starts_with("2\t人間\t"));
+    }
+
+    #[test]
+    fn success_output_base_pos_freq_and_tf_idf() {
+        count_base_pos_frequency_top10();
+        tf_idf_by_chapter();
+        assert!(Path::new("./data/chap04/tf_idf_chapter_top10.tsv").exists());
+    }
+
+    #[test]
+    fn success_kwic_from_reader() {
+        let mut out: Vec<u8> = vec![];
+        kwic_from(
+            Cursor::new(TOKENS_JSON),
+            &mut out,
+            Concordance::new(query_pattern("[base=見る]").unwrap()).with_window(2),
+            SortKey::Left,
+        );
+        assert_eq!(
+            "sentence\tleft\tkeyword\tright\n2\t社会を\t見\t\n1\t額を\t見\tた\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn success_output_kwic_cat() {
+        kwic_cat();
+        assert!(Path::new("./data/chap04/kwic_cat.tsv").exists());
+    }
 }
@@ -0,0 +1,406 @@
+use crate::render::{escape_xml, svg_text_width, FONT_FAMILY, FONT_SIZE};
+
+const BAR_COLOR: &str = "#4e79a7";
+const POINT_COLOR: &str = "#e15759";
+const LINE_COLOR: &str = "#59a14f";
+
+const MARGIN_LEFT: f64 = 70.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 40.0;
+const MARGIN_BOTTOM: f64 = 80.0;
+
+// SVG のグラフ（棒グラフ、ヒストグラム、両対数散布図）
+#[derive(Debug)]
+pub struct Chart {
+    title: String,
+    x_label: String,
+    y_label: String,
+    width: f64,
+    height: f64,
+}
+
+impl Chart {
+    pub fn new(title: &str) -> Chart {
+        Chart {
+            title: title.to_string(),
+            x_label: String::new(),
+            y_label: String::new(),
+            width: 640.0,
+            height: 400.0,
+        }
+    }
+
+    pub fn with_size(mut self, width: f64, height: f64) -> Chart {
+        self.width = width;
+        self.height = height;
+        return self;
+    }
+
+    pub fn with_labels(mut self, x_label: &str, y_label: &str) -> Chart {
+        self.x_label = x_label.to_string();
+        self.y_label = y_label.to_string();
+        return self;
+    }
+
+    fn plot_width(&self) -> f64 {
+        self.width - MARGIN_LEFT - MARGIN_RIGHT
+    }
+
+    fn plot_height(&self) -> f64 {
+        self.height - MARGIN_TOP - MARGIN_BOTTOM
+    }
+
+    // 値 0..max を描画領域の y 座標に変換する
+    fn y_position(&self, value: f64, max: f64) -> f64 {
+        MARGIN_TOP + self.plot_height() * (1.0 - value / max)
+    }
+
+    pub fn bar(&self, items: &[(&str, f64)]) -> String {
+        let mut svg = self.begin();
+        let max = nice_max(items.iter().map(|(_, value)| *value).fold(0.0, f64::max));
+        self.push_y_ticks(&mut svg, max);
+        let band = self.plot_width() / items.len().max(1) as f64;
+        // ラベルが帯に収まらなければ斜めにする
+        let rotate = items
+            .iter()
+            .any(|(label, _)| svg_text_width(label) > band * 0.9);
+        for (i, (label, value)) in items.iter().enumerate() {
+            let x = MARGIN_LEFT + band * i as f64;
+            let y = self.y_position(*value, max);
+            svg.push_str(&format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\"><title>{}: {}</title></rect>\n",
+                x + band * 0.1,
+                y,
+                band * 0.8,
+                MARGIN_TOP + self.plot_height() - y,
+                BAR_COLOR,
+                escape_xml(label),
+                format_number(*value)
+            ));
+            let label_x = x + band / 2.0;
+            let label_y = MARGIN_TOP + self.plot_height() + FONT_SIZE + 4.0;
+            if rotate {
+                svg.push_str(&format!(
+                    "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\" transform=\"rotate(-45 {:.1} {:.1})\">{}</text>\n",
+                    label_x, label_y, label_x, label_y, escape_xml(label)
+                ));
+            } else {
+                svg.push_str(&format!(
+                    "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n",
+                    label_x,
+                    label_y,
+                    escape_xml(label)
+                ));
+            }
+        }
+        self.push_axes(&mut svg);
+        return self.end(svg);
+    }
+
+    // values を bins 個の等幅の区間に分けて数える
+    pub fn histogram(&self, values: &[f64], bins: usize) -> String {
+        let bins = bins.max(1);
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let (min, max) = if values.is_empty() {
+            (0.0, 1.0)
+        } else if min == max {
+            (min - 0.5, max + 0.5)
+        } else {
+            (min, max)
+        };
+        let step = (max - min) / bins as f64;
+        let mut counts = vec![0usize; bins];
+        for value in values {
+            let bin = (((value - min) / step) as usize).min(bins - 1);
+            counts[bin] += 1;
+        }
+        let mut svg = self.begin();
+        let y_max = nice_max(*counts.iter().max().unwrap_or(&0) as f64);
+        self.push_y_ticks(&mut svg, y_max);
+        let band = self.plot_width() / bins as f64;
+        let label_every = ((bins as f64 * 40.0 / self.plot_width()).ceil() as usize).max(1);
+        for (i, count) in counts.iter().enumerate() {
+            let x = MARGIN_LEFT + band * i as f64;
+            let y = self.y_position(*count as f64, y_max);
+            svg.push_str(&format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"{}\" stroke=\"white\"><title>[{}, {}): {}</title></rect>\n",
+                x,
+                y,
+                band,
+                MARGIN_TOP + self.plot_height() - y,
+                BAR_COLOR,
+                format_number(min + step * i as f64),
+                format_number(min + step * (i + 1) as f64),
+                count
+            ));
+        }
+        for i in (0..=bins).step_by(label_every) {
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n",
+                MARGIN_LEFT + band * i as f64,
+                MARGIN_TOP + self.plot_height() + FONT_SIZE + 4.0,
+                format_number(min + step * i as f64)
+            ));
+        }
+        self.push_axes(&mut svg);
+        return self.end(svg);
+    }
+
+    // 両対数の散布図。fit に (指数, 切片) を渡すと log10 y = 切片 - 指数 * log10 x の直線を引く
+    pub fn log_log(&self, points: &[(f64, f64)], fit: Option<(f64, f64)>) -> String {
+        let logs: Vec<(f64, f64)> = points
+            .iter()
+            .filter(|(x, y)| *x > 0.0 && *y > 0.0)
+            .map(|(x, y)| (x.log10(), y.log10()))
+            .collect();
+        let x_max = logs
+            .iter()
+            .map(|(x, _)| *x)
+            .fold(0.0, f64::max)
+            .ceil()
+            .max(1.0);
+        let y_max = logs
+            .iter()
+            .map(|(_, y)| *y)
+            .fold(0.0, f64::max)
+            .ceil()
+            .max(1.0);
+        let x_min = logs
+            .iter()
+            .map(|(x, _)| *x)
+            .fold(x_max, f64::min)
+            .floor()
+            .min(0.0);
+        let y_min = logs
+            .iter()
+            .map(|(_, y)| *y)
+            .fold(y_max, f64::min)
+            .floor()
+            .min(0.0);
+        let to_x = |log_x: f64| MARGIN_LEFT + self.plot_width() * (log_x - x_min) / (x_max - x_min);
+        let to_y = |log_y: f64| {
+            MARGIN_TOP + self.plot_height() * (1.0 - (log_y - y_min) / (y_max - y_min))
+        };
+        let mut svg = self.begin();
+        for decade in (x_min as i32)..=(x_max as i32) {
+            let x = to_x(decade as f64);
+            svg.push_str(&format!(
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#dddddd\"/>\n",
+                x,
+                MARGIN_TOP,
+                x,
+                MARGIN_TOP + self.plot_height()
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n",
+                x,
+                MARGIN_TOP + self.plot_height() + FONT_SIZE + 4.0,
+                format_number(10f64.powi(decade))
+            ));
+        }
+        for decade in (y_min as i32)..=(y_max as i32) {
+            let y = to_y(decade as f64);
+            svg.push_str(&format!(
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#dddddd\"/>\n",
+                MARGIN_LEFT,
+                y,
+                MARGIN_LEFT + self.plot_width(),
+                y
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>\n",
+                MARGIN_LEFT - 6.0,
+                y + FONT_SIZE / 3.0,
+                format_number(10f64.powi(decade))
+            ));
+        }
+        for (x, y) in logs.iter() {
+            svg.push_str(&format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"2\" fill=\"{}\" fill-opacity=\"0.6\"/>\n",
+                to_x(*x),
+                to_y(*y),
+                POINT_COLOR
+            ));
+        }
+        if let Some((exponent, intercept)) = fit {
+            let line_y = |log_x: f64| intercept - exponent * log_x;
+            svg.push_str(&format!(
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-width=\"1.5\"/>\n",
+                to_x(x_min),
+                to_y(line_y(x_min)),
+                to_x(x_max),
+                to_y(line_y(x_max)),
+                LINE_COLOR
+            ));
+        }
+        self.push_axes(&mut svg);
+        return self.end(svg);
+    }
+
+    fn begin(&self) -> String {
+        let mut svg = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" xml:lang=\"ja\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"{font}\" font-size=\"{size}\">\n",
+            w = self.width,
+            h = self.height,
+            font = FONT_FAMILY,
+            size = FONT_SIZE
+        );
+        svg.push_str(&format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n",
+            self.width, self.height
+        ));
+        svg.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-size=\"{}\">{}</text>\n",
+            self.width / 2.0,
+            MARGIN_TOP / 2.0 + FONT_SIZE / 2.0,
+            FONT_SIZE + 4.0,
+            escape_xml(&self.title)
+        ));
+        return svg;
+    }
+
+    fn push_y_ticks(&self, svg: &mut String, max: f64) {
+        let step = nice_step(max / 5.0);
+        let mut value = 0.0;
+        while value <= max + step * 1.0e-9 {
+            let y = self.y_position(value, max);
+            svg.push_str(&format!(
+                "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"#dddddd\"/>\n",
+                MARGIN_LEFT,
+                y,
+                MARGIN_LEFT + self.plot_width(),
+                y
+            ));
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{}</text>\n",
+                MARGIN_LEFT - 6.0,
+                y + FONT_SIZE / 3.0,
+                format_number(value)
+            ));
+            value += step;
+        }
+    }
+
+    fn push_axes(&self, svg: &mut String) {
+        let bottom = MARGIN_TOP + self.plot_height();
+        svg.push_str(&format!(
+            "<path d=\"M{:.1} {:.1} V{:.1} H{:.1}\" fill=\"none\" stroke=\"black\"/>\n",
+            MARGIN_LEFT,
+            MARGIN_TOP,
+            bottom,
+            MARGIN_LEFT + self.plot_width()
+        ));
+        if !self.x_label.is_empty() {
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n",
+                MARGIN_LEFT + self.plot_width() / 2.0,
+                self.height - 8.0,
+                escape_xml(&self.x_label)
+            ));
+        }
+        if !self.y_label.is_empty() {
+            let x = 16.0;
+            let y = MARGIN_TOP + self.plot_height() / 2.0;
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" transform=\"rotate(-90 {:.1} {:.1})\">{}</text>\n",
+                x, y, x, y, escape_xml(&self.y_label)
+            ));
+        }
+    }
+
+    fn end(&self, mut svg: String) -> String {
+        svg.push_str("</svg>\n");
+        return svg;
+    }
+}
+
+// 1, 2, 5 × 10^n のうち raw 以上で最小の刻み
+fn nice_step(raw: f64) -> f64 {
+    if raw <= 0.0 {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let normalized = raw / magnitude;
+    let nice = if normalized <= 1.0 {
+        1.0
+    } else if normalized <= 2.0 {
+        2.0
+    } else if normalized <= 5.0 {
+        5.0
+    } else {
+        10.0
+    };
+    return nice * magnitude;
+}
+
+// 目盛りの刻みで割り切れるように最大値を切り上げる
+fn nice_max(max: f64) -> f64 {
+    if max <= 0.0 {
+        return 1.0;
+    }
+    let step = nice_step(max / 5.0);
+    return (max / step).ceil() * step;
+}
+
+fn format_number(value: f64) -> String {
+    if (value - value.round()).abs() < 1.0e-9 {
+        return format!("{}", value.round() as i64);
+    }
+    let formatted = format!("{:.2}", value);
+    return formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter04::chart::{format_number, nice_max, nice_step, Chart};
+
+    #[test]
+    pub fn success_nice_scale() {
+        assert_eq!(1.0, nice_step(0.7));
+        assert_eq!(20.0, nice_step(13.0));
+        assert_eq!(500.0, nice_step(320.0));
+        assert_eq!(1500.0, nice_max(1180.0));
+        assert_eq!(100.0, nice_max(100.0));
+        assert_eq!(1.0, nice_max(0.0));
+        assert_eq!("3", format_number(3.0));
+        assert_eq!("0.25", format_number(0.25));
+        assert_eq!("1.5", format_number(1.5));
+    }
+
+    #[test]
+    pub fn success_bar_chart() {
+        let svg = Chart::new("頻度上位 <10> 語")
+            .with_labels("語", "出現頻度")
+            .bar(&[("の", 9194.0), ("。", 7486.0), ("て", 6868.0), ("&", 1.0)]);
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.contains("xml:lang=\"ja\""));
+        assert!(svg.contains("Noto Sans CJK JP"));
+        assert!(svg.contains("頻度上位 &lt;10&gt; 語"));
+        assert!(svg.contains("<title>の: 9194</title>"));
+        assert!(svg.contains(">&amp;</text>"));
+        assert_eq!(4, svg.matches("<rect x=").count());
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    pub fn success_histogram_and_log_log() {
+        let values: Vec<f64> = vec![1.0, 1.0, 1.0, 2.0, 2.0, 3.0, 10.0];
+        let svg = Chart::new("ヒストグラム").histogram(&values, 3);
+        assert!(svg.contains("<title>[1, 4): 6</title>"));
+        assert!(svg.contains("<title>[7, 10): 1</title>"));
+
+        let points: Vec<(f64, f64)> = (1..=100)
+            .map(|rank| (rank as f64, 1000.0 / rank as f64))
+            .collect();
+        let svg = Chart::new("Zipf").log_log(&points, Some((1.0, 3.0)));
+        assert_eq!(100, svg.matches("<circle").count());
+        assert!(svg.contains(">1000</text>"));
+        assert!(svg.contains("stroke-width=\"1.5\""));
+        assert!(!Chart::new("空").log_log(&[], None).contains("<circle"));
+    }
+}
@@ -0,0 +1,349 @@
+use crate::chapter04::answer::Token;
+use crate::chapter04::command::Command;
+use crate::chapter04::term::{TermKey, TokenSelector};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::io::Write;
+use std::str::FromStr;
+
+// 共起を数える範囲
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Context {
+    // 同じ文に現れるか（文の数で数える）
+    Sentence,
+    // 対象語の前後 n 語以内に現れるか（語の数で数える）
+    Window(usize),
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Context::Sentence
+    }
+}
+
+// 共起語の順位付けに使う尺度
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Measure {
+    Frequency,
+    Pmi,
+    Ppmi,
+    LogLikelihood,
+    TScore,
+}
+
+impl FromStr for Measure {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "frequency" => Ok(Measure::Frequency),
+            "pmi" => Ok(Measure::Pmi),
+            "ppmi" => Ok(Measure::Ppmi),
+            "llr" => Ok(Measure::LogLikelihood),
+            "t-score" => Ok(Measure::TScore),
+            _ => Err(format!("unsupported measure: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Collocate {
+    pub term: String,
+    // 対象語との共起頻度
+    pub frequency: u64,
+    // 共起語自体の頻度
+    pub term_frequency: u64,
+    pub score: f64,
+}
+
+// 対象語の共起語を数える
+//
+// 文単位なら N は文の数、対象語と共起語の頻度はそれぞれを含む文の数。
+// 窓単位なら N は語の数、対象語側の頻度は窓に入った語の延べ数、共起語の頻度は出現回数。
+// 窓はストップワードなどを除いた後の語の並びで数え、文をまたがない。
+#[derive(Debug)]
+pub struct CoOccurrence {
+    target: String,
+    key: TermKey,
+    context: Context,
+    selector: TokenSelector,
+    total: u64,
+    target_count: u64,
+    target_total: u64,
+    term_counts: BTreeMap<String, u64>,
+    pair_counts: BTreeMap<String, u64>,
+}
+
+impl CoOccurrence {
+    pub fn new(target: &str) -> CoOccurrence {
+        CoOccurrence {
+            target: target.to_string(),
+            key: TermKey::default(),
+            context: Context::default(),
+            selector: TokenSelector::default(),
+            total: 0,
+            target_count: 0,
+            target_total: 0,
+            term_counts: BTreeMap::new(),
+            pair_counts: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_key(mut self, key: TermKey) -> CoOccurrence {
+        self.key = key;
+        return self;
+    }
+
+    pub fn with_context(mut self, context: Context) -> CoOccurrence {
+        self.context = context;
+        return self;
+    }
+
+    pub fn with_selector(mut self, selector: TokenSelector) -> CoOccurrence {
+        self.selector = selector;
+        return self;
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    // 対象語の出現回数
+    pub fn target_count(&self) -> u64 {
+        self.target_count
+    }
+
+    pub fn frequency(&self, term: &str) -> u64 {
+        *self.pair_counts.get(term).unwrap_or(&0)
+    }
+
+    pub fn counts(&self) -> &BTreeMap<String, u64> {
+        &self.pair_counts
+    }
+
+    pub fn score(&self, term: &str, measure: Measure) -> Option<f64> {
+        let observed = *self.pair_counts.get(term)?;
+        let term_total = *self.term_counts.get(term)?;
+        let table = ContingencyTable::new(observed, self.target_total, term_total, self.total);
+        return Some(match measure {
+            Measure::Frequency => observed as f64,
+            Measure::Pmi => table.pmi(),
+            Measure::Ppmi => table.pmi().max(0.0),
+            Measure::LogLikelihood => table.log_likelihood(),
+            Measure::TScore => table.t_score(),
+        });
+    }
+
+    // 共起頻度が min_frequency 以上の語を、尺度の降順（同点なら共起頻度の降順、語の順）に並べる
+    pub fn collocates(&self, measure: Measure, min_frequency: u64) -> Vec<Collocate> {
+        let mut collocates: Vec<Collocate> = self
+            .pair_counts
+            .iter()
+            .filter(|(_, frequency)| **frequency >= min_frequency)
+            .map(|(term, frequency)| Collocate {
+                term: term.to_string(),
+                frequency: *frequency,
+                term_frequency: *self.term_counts.get(term).unwrap_or(&0),
+                score: self.score(term, measure).unwrap_or(0.0),
+            })
+            .collect();
+        collocates.sort_by(|x, y| {
+            y.score
+                .partial_cmp(&x.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| y.frequency.cmp(&x.frequency))
+                .then_with(|| x.term.cmp(&y.term))
+        });
+        return collocates;
+    }
+
+    fn count_sentence(&mut self, terms: &[String]) {
+        let terms: BTreeSet<&String> = terms.iter().collect();
+        self.total += 1;
+        for term in terms.iter() {
+            *self.term_counts.entry(term.to_string()).or_insert(0) += 1;
+        }
+        if !terms.contains(&self.target) {
+            return;
+        }
+        self.target_total += 1;
+        for term in terms {
+            if *term != self.target {
+                *self.pair_counts.entry(term.to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn count_window(&mut self, terms: &[String], size: usize) {
+        self.total += terms.len() as u64;
+        for term in terms {
+            *self.term_counts.entry(term.to_string()).or_insert(0) += 1;
+        }
+        for i in 0..terms.len() {
+            if terms[i] != self.target {
+                continue;
+            }
+            let from = i.saturating_sub(size);
+            let to = (i + size).min(terms.len() - 1);
+            for j in (from..=to).filter(|j| *j != i) {
+                self.target_total += 1;
+                if terms[j] != self.target {
+                    *self.pair_counts.entry(terms[j].to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+}
+
+impl Command for CoOccurrence {
+    fn execute(&mut self, tokens: &Vec<Token>) {
+        // 対象語は選択条件に関わらず残す
+        let terms: Vec<String> = tokens
+            .iter()
+            .map(|token| (self.key.key(token), token))
+            .filter(|(term, token)| *term == self.target || self.selector.is_target(token))
+            .map(|(term, _)| term)
+            .collect();
+        self.target_count += terms.iter().filter(|term| **term == self.target).count() as u64;
+        match self.context {
+            Context::Sentence => self.count_sentence(&terms),
+            Context::Window(size) => self.count_window(&terms, size),
+        }
+    }
+}
+
+// 2×2 分割表。o11 = 共起頻度, r1 = 対象語側の合計, c1 = 共起語側の合計, n = 全体
+struct ContingencyTable {
+    observed: [f64; 4],
+    expected: [f64; 4],
+}
+
+impl ContingencyTable {
+    fn new(o11: u64, r1: u64, c1: u64, n: u64) -> ContingencyTable {
+        let (o11, r1, c1, n) = (o11 as f64, r1 as f64, c1 as f64, n as f64);
+        let r2 = n - r1;
+        let c2 = n - c1;
+        let o12 = (r1 - o11).max(0.0);
+        let o21 = (c1 - o11).max(0.0);
+        let o22 = (n - r1 - c1 + o11).max(0.0);
+        ContingencyTable {
+            observed: [o11, o12, o21, o22],
+            expected: [r1 * c1 / n, r1 * c2 / n, r2 * c1 / n, r2 * c2 / n],
+        }
+    }
+
+    fn pmi(&self) -> f64 {
+        (self.observed[0] / self.expected[0]).log2()
+    }
+
+    fn t_score(&self) -> f64 {
+        (self.observed[0] - self.expected[0]) / self.observed[0].sqrt()
+    }
+
+    // G^2 = 2 Σ O ln(O / E)
+    fn log_likelihood(&self) -> f64 {
+        2.0 * self
+            .observed
+            .iter()
+            .zip(self.expected.iter())
+            .filter(|(o, e)| **o > 0.0 && **e > 0.0)
+            .map(|(o, e)| o * (o / e).ln())
+            .sum::<f64>()
+    }
+}
+
+pub fn write_collocates<W: Write>(out: &mut W, collocates: &[Collocate]) -> io::Result<()> {
+    writeln!(out, "term\tfrequency\tterm_frequency\tscore")?;
+    for collocate in collocates {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{:.4}",
+            collocate.term, collocate.frequency, collocate.term_frequency, collocate.score
+        )?;
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter04::co_occurrence::{
+        write_collocates, CoOccurrence, Context, ContingencyTable, Measure,
+    };
+    use crate::chapter04::command::load_json;
+    use crate::chapter04::term::{TermKey, TokenSelector};
+    use crate::test_support::sentences_json;
+    use std::io::Cursor;
+
+    // 猫が鳴いた。 / 猫と犬が鳴く。 / 犬が走った。
+    fn sentences() -> String {
+        sentences_json(&[
+            "猫//名詞 が//助詞 鳴い/鳴く/動詞 た//助動詞 。//記号",
+            "猫//名詞 と//助詞 犬//名詞 が//助詞 鳴く//動詞 。//記号",
+            "犬//名詞 が//助詞 走っ/走る/動詞 た//助動詞 。//記号",
+        ])
+    }
+
+    #[test]
+    pub fn success_sentence_co_occurrence() {
+        let mut cmd = CoOccurrence::new("猫")
+            .with_key(TermKey::Base)
+            .with_selector(TokenSelector::content_words());
+        load_json(Cursor::new(sentences()), &mut cmd);
+        assert_eq!(2, cmd.target_count());
+        assert_eq!(2, cmd.frequency("鳴く"));
+        assert_eq!(1, cmd.frequency("犬"));
+        assert_eq!(0, cmd.frequency("が"));
+        assert_eq!(0, cmd.frequency("走る"));
+        // 鳴く は猫の文にしか現れない: log2(2 / (2 * 2 / 3))
+        let pmi = cmd.score("鳴く", Measure::Pmi).unwrap();
+        assert!((pmi - (1.5f64).log2()).abs() < 1.0e-9);
+        assert!(cmd.score("犬", Measure::Pmi).unwrap() < 0.0);
+        assert_eq!(0.0, cmd.score("犬", Measure::Ppmi).unwrap());
+        assert!(cmd.score("走る", Measure::Pmi).is_none());
+
+        let collocates = cmd.collocates(Measure::Ppmi, 1);
+        assert_eq!(
+            vec!["鳴く", "犬"],
+            collocates
+                .iter()
+                .map(|collocate| collocate.term.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(1, cmd.collocates(Measure::Frequency, 2).len());
+        let mut out: Vec<u8> = vec![];
+        write_collocates(&mut out, &collocates).unwrap();
+        assert_eq!(
+            "term\tfrequency\tterm_frequency\tscore\n鳴く\t2\t2\t0.5850\n犬\t1\t2\t0.0000\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn success_window_co_occurrence() {
+        let mut cmd = CoOccurrence::new("猫").with_context(Context::Window(1));
+        load_json(Cursor::new(sentences()), &mut cmd);
+        assert_eq!(1, cmd.frequency("が"));
+        assert_eq!(1, cmd.frequency("と"));
+        assert_eq!(0, cmd.frequency("鳴い"));
+
+        let mut cmd = CoOccurrence::new("猫")
+            .with_context(Context::Window(2))
+            .with_selector(TokenSelector::new().without_pos(&["助詞", "助動詞", "記号"]));
+        load_json(Cursor::new(sentences()), &mut cmd);
+        assert_eq!(1, cmd.frequency("鳴い"));
+        assert_eq!(1, cmd.frequency("犬"));
+        assert_eq!(1, cmd.frequency("鳴く"));
+        assert!(cmd.score("犬", Measure::LogLikelihood).unwrap() >= 0.0);
+        assert_eq!(Ok(Measure::TScore), "t-score".parse());
+        assert!("dice".parse::<Measure>().is_err());
+    }
+
+    #[test]
+    pub fn success_association_measures() {
+        // Manning & Schütze の例: new companies
+        let table = ContingencyTable::new(8, 15828, 4675, 14307668);
+        assert!((table.t_score() - 0.999932).abs() < 1.0e-6);
+        assert!((table.pmi() - 0.6293).abs() < 1.0e-3);
+        assert!(table.log_likelihood() > 0.0);
+    }
+}
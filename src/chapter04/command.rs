@@ -0,0 +1,385 @@
+use crate::chapter04::answer::Token;
+use crate::chapter04::token_store::{TokenRef, TokenStore, TokenStoreError};
+use regex::Regex;
+use std::io::BufRead;
+
+// 1文分のトークン列を受け取って処理する
+pub trait Command {
+    fn execute(&mut self, tokens: &Vec<Token>);
+}
+
+impl<T: Command + ?Sized> Command for &mut T {
+    fn execute(&mut self, tokens: &Vec<Token>) {
+        (**self).execute(tokens);
+    }
+}
+
+impl<T: Command + ?Sized> Command for Box<T> {
+    fn execute(&mut self, tokens: &Vec<Token>) {
+        (**self).execute(tokens);
+    }
+}
+
+// 複数のコマンドを1回の読み込みで順に実行する
+impl<T: Command> Command for Vec<T> {
+    fn execute(&mut self, tokens: &Vec<Token>) {
+        self.iter_mut().for_each(|cmd| cmd.execute(tokens));
+    }
+}
+
+impl<A: Command, B: Command> Command for (A, B) {
+    fn execute(&mut self, tokens: &Vec<Token>) {
+        self.0.execute(tokens);
+        self.1.execute(tokens);
+    }
+}
+
+// 対象の文だけをコマンドに渡す。コマンドごとに違う条件で1回の読み込みにまとめるときに使う
+pub struct FilteredCommand<C: Command, F: Filter> {
+    command: C,
+    filter: F,
+}
+
+impl<C: Command, F: Filter> FilteredCommand<C, F> {
+    pub fn new(command: C, filter: F) -> FilteredCommand<C, F> {
+        FilteredCommand { command, filter }
+    }
+
+    pub fn into_inner(self) -> C {
+        self.command
+    }
+}
+
+impl<C: Command, F: Filter> Command for FilteredCommand<C, F> {
+    fn execute(&mut self, tokens: &Vec<Token>) {
+        if self.filter.is_target(tokens) {
+            self.command.execute(tokens);
+        }
+    }
+}
+
+// 1文分のトークン列を処理対象にするか判定する
+pub trait Filter {
+    fn is_target(&self, tokens: &Vec<Token>) -> bool;
+
+    fn and<F: Filter>(self, other: F) -> And<Self, F>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+
+    fn or<F: Filter>(self, other: F) -> Or<Self, F>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+
+    fn not(self) -> Not<Self>
+    where
+        Self: Sized,
+    {
+        Not(self)
+    }
+}
+
+impl<F: Fn(&Vec<Token>) -> bool> Filter for F {
+    fn is_target(&self, tokens: &Vec<Token>) -> bool {
+        self(tokens)
+    }
+}
+
+pub struct And<A: Filter, B: Filter>(A, B);
+
+impl<A: Filter, B: Filter> Filter for And<A, B> {
+    fn is_target(&self, tokens: &Vec<Token>) -> bool {
+        self.0.is_target(tokens) && self.1.is_target(tokens)
+    }
+}
+
+pub struct Or<A: Filter, B: Filter>(A, B);
+
+impl<A: Filter, B: Filter> Filter for Or<A, B> {
+    fn is_target(&self, tokens: &Vec<Token>) -> bool {
+        self.0.is_target(tokens) || self.1.is_target(tokens)
+    }
+}
+
+pub struct Not<A: Filter>(A);
+
+impl<A: Filter> Filter for Not<A> {
+    fn is_target(&self, tokens: &Vec<Token>) -> bool {
+        !self.0.is_target(tokens)
+    }
+}
+
+pub struct NonFilter {}
+
+impl Filter for NonFilter {
+    fn is_target(&self, _tokens: &Vec<Token>) -> bool {
+        true
+    }
+}
+
+// 表層形が一致するトークンを含む文
+pub struct SurfaceFilter {
+    surface: String,
+}
+
+impl SurfaceFilter {
+    pub fn new(surface: &str) -> SurfaceFilter {
+        SurfaceFilter {
+            surface: surface.to_string(),
+        }
+    }
+}
+
+impl Filter for SurfaceFilter {
+    fn is_target(&self, tokens: &Vec<Token>) -> bool {
+        tokens.iter().any(|token| token.surface() == self.surface)
+    }
+}
+
+// 原形が一致するトークンを含む文
+pub struct BaseFilter {
+    base: String,
+}
+
+impl BaseFilter {
+    pub fn new(base: &str) -> BaseFilter {
+        BaseFilter {
+            base: base.to_string(),
+        }
+    }
+}
+
+impl Filter for BaseFilter {
+    fn is_target(&self, tokens: &Vec<Token>) -> bool {
+        tokens.iter().any(|token| token.base() == self.base)
+    }
+}
+
+// 品詞（と品詞細分類1）が一致するトークンを含む文
+pub struct PosFilter {
+    pos: String,
+    pos1: Option<String>,
+}
+
+impl PosFilter {
+    pub fn new(pos: &str) -> PosFilter {
+        PosFilter {
+            pos: pos.to_string(),
+            pos1: None,
+        }
+    }
+
+    pub fn with_pos1(mut self, pos1: &str) -> PosFilter {
+        self.pos1 = Some(pos1.to_string());
+        return self;
+    }
+}
+
+impl Filter for PosFilter {
+    fn is_target(&self, tokens: &Vec<Token>) -> bool {
+        tokens.iter().any(|token| {
+            token.pos() == self.pos
+                && self
+                    .pos1
+                    .as_ref()
+                    .map(|pos1| token.pos1() == pos1)
+                    .unwrap_or(true)
+        })
+    }
+}
+
+// 表層形をつないだ文に正規表現がマッチする文
+pub struct RegexFilter {
+    regex: Regex,
+}
+
+impl RegexFilter {
+    pub fn new(pattern: &str) -> Result<RegexFilter, regex::Error> {
+        Ok(RegexFilter {
+            regex: Regex::new(pattern)?,
+        })
+    }
+}
+
+impl Filter for RegexFilter {
+    fn is_target(&self, tokens: &Vec<Token>) -> bool {
+        let text: String = tokens.iter().map(|token| token.surface()).collect();
+        self.regex.is_match(text.as_str())
+    }
+}
+
+// ch04-30. 形態素解析結果の読み込み
+pub fn load_json<R: BufRead, T: Command>(input: R, cmd: &mut T) {
+    load_json_with_filter(input, cmd, &NonFilter {});
+}
+
+pub fn load_json_with_filter<R: BufRead, T: Command, U: Filter>(input: R, cmd: &mut T, filter: &U) {
+    input
+        .lines()
+        .filter_map(|item| item.ok())
+        .map(|line| parse_line_json(line.as_str()))
+        .filter(|tokens| filter.is_target(tokens))
+        .for_each(|tokens| cmd.execute(&tokens));
+}
+
+pub fn parse_line_json(line: &str) -> Vec<Token> {
+    return try_parse_line_json(line).unwrap();
+}
+
+pub fn try_parse_line_json(line: &str) -> Result<Vec<Token>, serde_json::Error> {
+    return serde_json::from_str(line);
+}
+
+// 1文分のトークン列を、バイナリ形式のバッファを借用したまま受け取って処理する。
+// 文字列はバッファ（'a）を指すので、文をまたいで持っておける
+pub trait StoreCommand<'a> {
+    fn execute(&mut self, tokens: &[TokenRef<'a>]);
+}
+
+impl<'a, F: FnMut(&[TokenRef<'a>])> StoreCommand<'a> for F {
+    fn execute(&mut self, tokens: &[TokenRef<'a>]) {
+        self(tokens);
+    }
+}
+
+// バイナリ形式（token_store）から、文字列をコピーせずに読み込む
+pub fn load_store_refs<'a, T: StoreCommand<'a>>(
+    store: &TokenStore<'a>,
+    cmd: &mut T,
+) -> Result<(), TokenStoreError> {
+    for sentence in store.sentences() {
+        cmd.execute(&sentence?);
+    }
+    return Ok(());
+}
+
+// バイナリ形式から読み込み、Token に変換して（文字列をコピーして）Command に渡す
+pub fn load_store<T: Command>(store: &TokenStore, cmd: &mut T) {
+    load_store_with_filter(store, cmd, &NonFilter {});
+}
+
+pub fn load_store_with_filter<T: Command, U: Filter>(store: &TokenStore, cmd: &mut T, filter: &U) {
+    load_store_refs(store, &mut |refs: &[TokenRef]| {
+        let tokens: Vec<Token> = refs.iter().map(Into::into).collect();
+        if filter.is_target(&tokens) {
+            cmd.execute(&tokens);
+        }
+    })
+    .expect("broken token store");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter04::answer::Token;
+    use crate::chapter04::command::{
+        load_json, load_json_with_filter, load_store_refs, load_store_with_filter, parse_line_json,
+        BaseFilter, Command, Filter, FilteredCommand, NonFilter, PosFilter, RegexFilter,
+        SurfaceFilter,
+    };
+    use crate::chapter04::token_store::{TokenRef, TokenStore, TokenStoreWriter};
+    use std::io::Cursor;
+
+    const SENTENCES: &str = "[{\"surface\":\"猫\",\"base\":\"猫\",\"pos\":\"名詞\",\"pos1\":\"一般\"},\
+                             {\"surface\":\"が\",\"base\":\"が\",\"pos\":\"助詞\",\"pos1\":\"格助詞\"},\
+                             {\"surface\":\"い\",\"base\":\"いる\",\"pos\":\"動詞\",\"pos1\":\"自立\"},\
+                             {\"surface\":\"た\",\"base\":\"た\",\"pos\":\"助動詞\",\"pos1\":\"\"}]\n\
+                             [{\"surface\":\"犬\",\"base\":\"犬\",\"pos\":\"名詞\",\"pos1\":\"一般\"},\
+                             {\"surface\":\"が\",\"base\":\"が\",\"pos\":\"助詞\",\"pos1\":\"格助詞\"},\
+                             {\"surface\":\"いる\",\"base\":\"いる\",\"pos\":\"動詞\",\"pos1\":\"自立\"}]\n\
+                             [{\"surface\":\"三毛猫\",\"base\":\"三毛猫\",\"pos\":\"名詞\",\"pos1\":\"一般\"}]\n";
+
+    // 受け取った文を表層形をつないで記録する
+    #[derive(Default)]
+    struct Recorder {
+        sentences: Vec<String>,
+    }
+
+    impl Command for Recorder {
+        fn execute(&mut self, tokens: &Vec<Token>) {
+            self.sentences
+                .push(tokens.iter().map(|token| token.surface()).collect());
+        }
+    }
+
+    fn run<F: Filter>(filter: F) -> Vec<String> {
+        let mut recorder = Recorder::default();
+        load_json_with_filter(Cursor::new(SENTENCES), &mut recorder, &filter);
+        return recorder.sentences;
+    }
+
+    // バッファを指す表層形を文をまたいで集める
+    fn surfaces<'a>(store: &TokenStore<'a>) -> Vec<&'a str> {
+        let mut surfaces = vec![];
+        load_store_refs(store, &mut |tokens: &[TokenRef<'a>]| {
+            surfaces.extend(tokens.iter().map(|token| token.surface));
+        })
+        .unwrap();
+        return surfaces;
+    }
+
+    #[test]
+    pub fn success_load_store() {
+        let mut writer = TokenStoreWriter::new();
+        SENTENCES
+            .lines()
+            .for_each(|line| writer.push_sentence(&parse_line_json(line)).unwrap());
+        let mut bytes = vec![];
+        writer.finish(&mut bytes).unwrap();
+        let store = TokenStore::open(&bytes).unwrap();
+
+        let surfaces = surfaces(&store);
+        assert_eq!(8, surfaces.len());
+        assert_eq!("三毛猫", surfaces[7]);
+
+        let mut recorder = Recorder::default();
+        load_store_with_filter(&store, &mut recorder, &SurfaceFilter::new("犬"));
+        assert_eq!(vec!["犬がいる"], recorder.sentences);
+    }
+
+    #[test]
+    pub fn success_filters() {
+        assert_eq!(3, run(NonFilter {}).len());
+        assert_eq!(vec!["猫がいた"], run(SurfaceFilter::new("猫")));
+        assert_eq!(vec!["猫がいた", "犬がいる"], run(BaseFilter::new("いる")));
+        assert_eq!(3, run(PosFilter::new("名詞").with_pos1("一般")).len());
+        assert!(run(PosFilter::new("名詞").with_pos1("固有名詞")).is_empty());
+        assert_eq!(
+            vec!["猫がいた", "三毛猫"],
+            run(RegexFilter::new("猫").unwrap())
+        );
+        assert!(RegexFilter::new("(").is_err());
+    }
+
+    #[test]
+    pub fn success_filter_combinators() {
+        assert_eq!(
+            vec!["猫がいた"],
+            run(BaseFilter::new("いる").and(RegexFilter::new("猫").unwrap()))
+        );
+        assert_eq!(
+            vec!["猫がいた", "犬がいる"],
+            run(SurfaceFilter::new("猫").or(SurfaceFilter::new("犬")))
+        );
+        assert_eq!(vec!["三毛猫"], run(BaseFilter::new("いる").not()));
+        assert_eq!(vec!["三毛猫"], run(|tokens: &Vec<Token>| tokens.len() == 1));
+    }
+
+    #[test]
+    pub fn success_several_commands_in_one_pass() {
+        let mut all = Recorder::default();
+        let mut cats = FilteredCommand::new(Recorder::default(), RegexFilter::new("猫").unwrap());
+        let mut dogs = FilteredCommand::new(Recorder::default(), SurfaceFilter::new("犬"));
+        {
+            let mut commands: Vec<&mut dyn Command> = vec![&mut all, &mut cats, &mut dogs];
+            load_json(Cursor::new(SENTENCES), &mut commands);
+        }
+        assert_eq!(3, all.sentences.len());
+        assert_eq!(vec!["猫がいた", "三毛猫"], cats.into_inner().sentences);
+        assert_eq!(vec!["犬がいる"], dogs.into_inner().sentences);
+    }
+}
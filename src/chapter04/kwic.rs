@@ -0,0 +1,288 @@
+use crate::chapter04::answer::Token;
+use crate::chapter04::command::Command;
+use crate::chapter04::pattern::{PatternError, TokenPattern};
+use crate::render::text_width;
+use std::cmp::Ordering;
+use std::io;
+use std::io::Write;
+use std::str::FromStr;
+
+// KWIC の並べ方
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SortKey {
+    // 出現順
+    None,
+    // 直前の語から順に比べる
+    Left,
+    // 直後の語から順に比べる
+    Right,
+}
+
+impl Default for SortKey {
+    fn default() -> Self {
+        SortKey::None
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(SortKey::None),
+            "left" => Ok(SortKey::Left),
+            "right" => Ok(SortKey::Right),
+            _ => Err(format!("unsupported sort key: {}", s)),
+        }
+    }
+}
+
+// [ か ( から始まればトークンパターン、それ以外は表層形として扱う
+pub fn query_pattern(query: &str) -> Result<TokenPattern, PatternError> {
+    if query.starts_with('[') || query.starts_with('(') {
+        return TokenPattern::new(query);
+    }
+    let escaped = query.replace('\\', "\\\\").replace('"', "\\\"");
+    return TokenPattern::new(&format!("[surface=\"{}\"]", escaped));
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct KwicLine {
+    // 何番目（1始まり）の文か。入力の行番号ではなく、execute に渡された文を数えたもの
+    pub sentence: usize,
+    pub left: Vec<String>,
+    pub keyword: Vec<String>,
+    pub right: Vec<String>,
+}
+
+impl KwicLine {
+    pub fn left_text(&self) -> String {
+        self.left.concat()
+    }
+
+    pub fn keyword_text(&self) -> String {
+        self.keyword.concat()
+    }
+
+    pub fn right_text(&self) -> String {
+        self.right.concat()
+    }
+}
+
+// 検索語の前後 window 語を集める
+#[derive(Debug)]
+pub struct Concordance {
+    pattern: TokenPattern,
+    window: usize,
+    sentences: usize,
+    lines: Vec<KwicLine>,
+}
+
+impl Concordance {
+    pub fn new(pattern: TokenPattern) -> Concordance {
+        Concordance {
+            pattern,
+            window: 5,
+            sentences: 0,
+            lines: vec![],
+        }
+    }
+
+    pub fn with_window(mut self, window: usize) -> Concordance {
+        self.window = window;
+        return self;
+    }
+
+    pub fn lines(&self) -> &[KwicLine] {
+        &self.lines
+    }
+
+    // 同じ文脈なら検索語、出現順で並べる
+    pub fn sort(&mut self, key: SortKey) {
+        let compare_context = |x: &KwicLine, y: &KwicLine| -> Ordering {
+            match key {
+                SortKey::None => Ordering::Equal,
+                SortKey::Left => x.left.iter().rev().cmp(y.left.iter().rev()),
+                SortKey::Right => x.right.cmp(&y.right),
+            }
+        };
+        self.lines.sort_by(|x, y| {
+            compare_context(x, y)
+                .then_with(|| match key {
+                    SortKey::None => Ordering::Equal,
+                    _ => x.keyword.cmp(&y.keyword),
+                })
+                .then_with(|| x.sentence.cmp(&y.sentence))
+        });
+    }
+
+    // 検索語の列が揃うように左の文脈を右寄せする
+    pub fn write_aligned<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let left_width = self
+            .lines
+            .iter()
+            .map(|line| display_width(&line.left_text()))
+            .max()
+            .unwrap_or(0);
+        let keyword_width = self
+            .lines
+            .iter()
+            .map(|line| display_width(&line.keyword_text()))
+            .max()
+            .unwrap_or(0);
+        for line in self.lines.iter() {
+            let left = line.left_text();
+            let keyword = line.keyword_text();
+            writeln!(
+                out,
+                "{}{}  {}{}  {}",
+                " ".repeat(left_width - display_width(&left)),
+                left,
+                keyword,
+                " ".repeat(keyword_width - display_width(&keyword)),
+                line.right_text()
+            )?;
+        }
+        return Ok(());
+    }
+
+    pub fn write_tsv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "sentence\tleft\tkeyword\tright")?;
+        for line in self.lines.iter() {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}",
+                line.sentence,
+                escape_tsv(&line.left_text()),
+                escape_tsv(&line.keyword_text()),
+                escape_tsv(&line.right_text())
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+impl Command for Concordance {
+    fn execute(&mut self, tokens: &Vec<Token>) {
+        self.sentences += 1;
+        let surfaces = |tokens: &[Token]| -> Vec<String> {
+            tokens
+                .iter()
+                .map(|token| token.surface().to_string())
+                .collect()
+        };
+        for found in self.pattern.find_iter(tokens) {
+            let from = found.start.saturating_sub(self.window);
+            let to = (found.end + self.window).min(tokens.len());
+            self.lines.push(KwicLine {
+                sentence: self.sentences,
+                left: surfaces(&tokens[from..found.start]),
+                keyword: surfaces(found.tokens(tokens)),
+                right: surfaces(&tokens[found.end..to]),
+            });
+        }
+    }
+}
+
+// 端末での表示幅（桁数）。半角は1桁、全角は2桁
+fn display_width(text: &str) -> usize {
+    (text_width(text, 0.5) * 2.0) as usize
+}
+
+fn escape_tsv(text: &str) -> String {
+    text.replace('\t', " ").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter04::command::load_json;
+    use crate::chapter04::kwic::{display_width, query_pattern, Concordance, SortKey};
+    use crate::test_support::{sentences_json, tokens};
+    use std::io::Cursor;
+
+    fn sentences() -> String {
+        sentences_json(&[
+            "吾輩 は 猫 で ある",
+            "その 猫 が 鳴く",
+            "猫 だ",
+            "白い 猫 と 黒い 猫",
+        ])
+    }
+
+    fn run(query: &str, window: usize, key: SortKey) -> Concordance {
+        let mut cmd = Concordance::new(query_pattern(query).unwrap()).with_window(window);
+        load_json(Cursor::new(sentences()), &mut cmd);
+        cmd.sort(key);
+        return cmd;
+    }
+
+    #[test]
+    pub fn success_kwic_sort() {
+        let cmd = run("猫", 2, SortKey::None);
+        assert_eq!(5, cmd.lines().len());
+        assert_eq!(
+            vec![1, 2, 3, 4, 4],
+            cmd.lines()
+                .iter()
+                .map(|line| line.sentence)
+                .collect::<Vec<usize>>()
+        );
+        assert_eq!(vec!["吾輩", "は"], cmd.lines()[0].left);
+        assert_eq!(vec!["で", "ある"], cmd.lines()[0].right);
+        assert!(cmd.lines()[2].left.is_empty());
+
+        let right: Vec<String> = run("猫", 1, SortKey::Right)
+            .lines()
+            .iter()
+            .map(|line| line.right_text())
+            .collect();
+        assert_eq!(vec!["", "が", "だ", "で", "と"], right);
+
+        // 直前の語で比べ、同じなら更に前の語で比べる
+        let left: Vec<String> = run("猫", 2, SortKey::Left)
+            .lines()
+            .iter()
+            .map(|line| line.left_text())
+            .collect();
+        assert_eq!(vec!["", "その", "吾輩は", "白い", "と黒い"], left);
+    }
+
+    #[test]
+    pub fn success_kwic_output() {
+        let cmd = run("[surface=白い] [surface=猫]", 1, SortKey::None);
+        assert_eq!(1, cmd.lines().len());
+        assert_eq!("白い猫", cmd.lines()[0].keyword_text());
+
+        let cmd = run("猫", 1, SortKey::None);
+        let mut out: Vec<u8> = vec![];
+        cmd.write_tsv(&mut out).unwrap();
+        let tsv = String::from_utf8(out).unwrap();
+        assert_eq!(
+            "sentence\tleft\tkeyword\tright\n1\tは\t猫\tで\n2\tその\t猫\tが\n",
+            tsv.lines()
+                .take(3)
+                .map(|l| format!("{}\n", l))
+                .collect::<String>()
+        );
+
+        let mut out: Vec<u8> = vec![];
+        cmd.write_aligned(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!("  は  猫  で", lines[0]);
+        assert_eq!("その  猫  が", lines[1]);
+        assert_eq!(4, display_width("白い"));
+        assert_eq!(5, display_width("ﾈｺ猫a"));
+        assert!(query_pattern("[pos=").is_err());
+    }
+
+    #[test]
+    pub fn success_query_pattern_escape() {
+        let tokens = tokens("\"猫\" a\\b");
+        assert_eq!("\"猫\"", tokens[0].surface());
+        assert_eq!("a\\b", tokens[1].surface());
+        assert!(query_pattern("\"猫\"").unwrap().is_match(&tokens));
+        assert!(query_pattern("a\\b").unwrap().is_match(&tokens));
+        assert!(!query_pattern("猫").unwrap().is_match(&tokens));
+    }
+}
@@ -0,0 +1,600 @@
+use crate::chapter04::answer::Token;
+use regex::Regex;
+use std::fmt;
+
+// トークン列に対するパターン
+//
+//   [pos=名詞] [surface=の] [pos=名詞]
+//   [pos=名詞 & pos1!=代名詞]+ [base~^(する|なる)$]
+//   ([pos=形容詞] | [pos=連体詞])? [pos=名詞]{2,}
+//
+// [] の中は「キー 演算子 値」を & でつなぐ。演算子は = != ~（正規表現）。[] は任意の1トークン
+// 値に空白や & ] を含めるときは "..." で囲む
+// 量指定子は ? * + {n} {n,} {n,m}、() でまとめ、| で選択する
+// Thompson 法で NFA にして、左端最長で重ならないように探す
+
+#[derive(Debug, PartialEq)]
+pub struct PatternError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid pattern at {}: {}", self.position, self.message)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+// マッチしたトークンの範囲 [start, end)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PatternMatch {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl PatternMatch {
+    pub fn tokens<'a>(&self, tokens: &'a [Token]) -> &'a [Token] {
+        &tokens[self.start..self.end]
+    }
+
+    // マッチした部分の表層形をつないだ文字列
+    pub fn surface(&self, tokens: &[Token]) -> String {
+        self.tokens(tokens)
+            .iter()
+            .map(|token| token.surface())
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+enum Operator {
+    Equal(String),
+    NotEqual(String),
+    Regex(Regex),
+}
+
+#[derive(Debug)]
+struct Condition {
+    field: fn(&Token) -> &str,
+    operator: Operator,
+}
+
+impl Condition {
+    fn is_match(&self, token: &Token) -> bool {
+        let value = (self.field)(token);
+        match &self.operator {
+            Operator::Equal(expected) => value == expected,
+            Operator::NotEqual(expected) => value != expected,
+            Operator::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+fn field_accessor(key: &str) -> Option<fn(&Token) -> &str> {
+    let accessor: fn(&Token) -> &str = match key {
+        "surface" => Token::surface,
+        "base" => Token::base,
+        "pos" => Token::pos,
+        "pos1" => Token::pos1,
+        "pos2" => Token::pos2,
+        "pos3" => Token::pos3,
+        "conjugation_type" => Token::conjugation_type,
+        "conjugation_form" => Token::conjugation_form,
+        "reading" => Token::reading,
+        "pronunciation" => Token::pronunciation,
+        _ => return None,
+    };
+    return Some(accessor);
+}
+
+// 構文木
+#[derive(Debug)]
+enum Node {
+    Atom(Vec<Condition>),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    position: usize,
+    source: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &str) -> Result<T, PatternError> {
+        Err(PatternError {
+            position: self.position,
+            message: format!("{} in \"{}\"", message, self.source),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), PatternError> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            return Ok(());
+        }
+        return self.error(format!("expected '{}'", expected).as_str());
+    }
+
+    // alternation := sequence ('|' sequence)*
+    fn parse_alternation(&mut self) -> Result<Node, PatternError> {
+        let mut branches = vec![self.parse_sequence()?];
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('|') {
+                break;
+            }
+            self.position += 1;
+            branches.push(self.parse_sequence()?);
+        }
+        if branches.len() == 1 {
+            return Ok(branches.pop().unwrap());
+        }
+        return Ok(Node::Alternate(branches));
+    }
+
+    // sequence := (element quantifier?)+
+    fn parse_sequence(&mut self) -> Result<Node, PatternError> {
+        let mut elements = vec![];
+        loop {
+            self.skip_whitespace();
+            let element = match self.peek() {
+                Some('[') => self.parse_atom()?,
+                Some('(') => {
+                    self.position += 1;
+                    let inner = self.parse_alternation()?;
+                    self.expect(')')?;
+                    inner
+                }
+                _ => break,
+            };
+            elements.push(self.parse_quantifier(element)?);
+        }
+        if elements.is_empty() {
+            return self.error("expected '[' or '('");
+        }
+        if elements.len() == 1 {
+            return Ok(elements.pop().unwrap());
+        }
+        return Ok(Node::Concat(elements));
+    }
+
+    fn parse_quantifier(&mut self, node: Node) -> Result<Node, PatternError> {
+        let (min, max) = match self.peek() {
+            Some('?') => (0, Some(1)),
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('{') => {
+                self.position += 1;
+                let min = self.parse_number()?;
+                let max = if self.peek() == Some(',') {
+                    self.position += 1;
+                    if self.peek() == Some('}') {
+                        None
+                    } else {
+                        Some(self.parse_number()?)
+                    }
+                } else {
+                    Some(min)
+                };
+                if self.peek() != Some('}') {
+                    return self.error("expected '}'");
+                }
+                if max.map(|max| max < min).unwrap_or(false) {
+                    return self.error("max is smaller than min");
+                }
+                (min, max)
+            }
+            _ => return Ok(node),
+        };
+        self.position += 1;
+        return Ok(Node::Repeat {
+            node: Box::new(node),
+            min,
+            max,
+        });
+    }
+
+    fn parse_number(&mut self) -> Result<usize, PatternError> {
+        let start = self.position;
+        while self.peek().map(|c| c.is_ascii_digit()).unwrap_or(false) {
+            self.position += 1;
+        }
+        let digits: String = self.chars[start..self.position].iter().collect();
+        match digits.parse() {
+            Ok(number) => Ok(number),
+            Err(_) => self.error("expected a number"),
+        }
+    }
+
+    // atom := '[' (condition ('&' condition)*)? ']'
+    fn parse_atom(&mut self) -> Result<Node, PatternError> {
+        self.position += 1;
+        let mut conditions = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Node::Atom(conditions));
+        }
+        loop {
+            conditions.push(self.parse_condition()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some('&') => self.position += 1,
+                Some(']') => {
+                    self.position += 1;
+                    return Ok(Node::Atom(conditions));
+                }
+                _ => return self.error("expected '&' or ']'"),
+            }
+        }
+    }
+
+    fn parse_condition(&mut self) -> Result<Condition, PatternError> {
+        self.skip_whitespace();
+        let start = self.position;
+        while self
+            .peek()
+            .map(|c| c.is_ascii_alphanumeric() || c == '_')
+            .unwrap_or(false)
+        {
+            self.position += 1;
+        }
+        let key: String = self.chars[start..self.position].iter().collect();
+        let field = match field_accessor(key.as_str()) {
+            Some(field) => field,
+            None => {
+                self.position = start;
+                return self.error(format!("unknown key \"{}\"", key).as_str());
+            }
+        };
+        self.skip_whitespace();
+        let operator = match (self.peek(), self.chars.get(self.position + 1)) {
+            (Some('!'), Some('=')) => {
+                self.position += 2;
+                "!="
+            }
+            (Some('='), _) => {
+                self.position += 1;
+                "="
+            }
+            (Some('~'), _) => {
+                self.position += 1;
+                "~"
+            }
+            _ => return self.error("expected '=', '!=' or '~'"),
+        };
+        let value = self.parse_value()?;
+        let operator = match operator {
+            "=" => Operator::Equal(value),
+            "!=" => Operator::NotEqual(value),
+            _ => match Regex::new(value.as_str()) {
+                Ok(regex) => Operator::Regex(regex),
+                Err(e) => return self.error(format!("invalid regex: {}", e).as_str()),
+            },
+        };
+        return Ok(Condition { field, operator });
+    }
+
+    fn parse_value(&mut self) -> Result<String, PatternError> {
+        self.skip_whitespace();
+        let mut value = String::new();
+        if self.peek() == Some('"') {
+            self.position += 1;
+            loop {
+                match self.peek() {
+                    Some('"') => {
+                        self.position += 1;
+                        return Ok(value);
+                    }
+                    Some('\\') if self.chars.get(self.position + 1).is_some() => {
+                        value.push(self.chars[self.position + 1]);
+                        self.position += 2;
+                    }
+                    Some(c) => {
+                        value.push(c);
+                        self.position += 1;
+                    }
+                    None => return self.error("unterminated string"),
+                }
+            }
+        }
+        while let Some(c) = self.peek() {
+            if c == '&' || c == ']' || c.is_whitespace() {
+                break;
+            }
+            value.push(c);
+            self.position += 1;
+        }
+        return Ok(value);
+    }
+}
+
+// NFA の状態
+#[derive(Debug)]
+enum State {
+    Token(usize, usize),
+    Split(usize, usize),
+    Jump(usize),
+    Accept,
+}
+
+#[derive(Debug)]
+pub struct TokenPattern {
+    atoms: Vec<Vec<Condition>>,
+    states: Vec<State>,
+    start: usize,
+}
+
+impl TokenPattern {
+    pub fn new(pattern: &str) -> Result<TokenPattern, PatternError> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            position: 0,
+            source: pattern,
+        };
+        let node = parser.parse_alternation()?;
+        parser.skip_whitespace();
+        if parser.position < parser.chars.len() {
+            return parser.error("unexpected character");
+        }
+        let mut compiled = TokenPattern {
+            atoms: vec![],
+            states: vec![],
+            start: 0,
+        };
+        let accept = compiled.push(State::Accept);
+        compiled.start = compiled.compile(node, accept);
+        return Ok(compiled);
+    }
+
+    fn push(&mut self, state: State) -> usize {
+        self.states.push(state);
+        return self.states.len() - 1;
+    }
+
+    // node を next に続く状態列にして、その入口を返す（後ろから組み立てる）
+    fn compile(&mut self, node: Node, next: usize) -> usize {
+        match node {
+            Node::Atom(conditions) => {
+                self.atoms.push(conditions);
+                let atom = self.atoms.len() - 1;
+                self.push(State::Token(atom, next))
+            }
+            Node::Concat(nodes) => nodes
+                .into_iter()
+                .rev()
+                .fold(next, |next, node| self.compile(node, next)),
+            Node::Alternate(nodes) => {
+                let entries: Vec<usize> = nodes
+                    .into_iter()
+                    .map(|node| self.compile(node, next))
+                    .collect();
+                entries
+                    .into_iter()
+                    .rev()
+                    .fold(None, |rest, entry| match rest {
+                        None => Some(entry),
+                        Some(rest) => Some(self.push(State::Split(entry, rest))),
+                    })
+                    .unwrap()
+            }
+            Node::Repeat { node, min, max } => {
+                // 必須部分 min 回のあと、任意部分（上限なしならループ）を続ける
+                let mut entry = match max {
+                    None => {
+                        let split = self.push(State::Jump(next));
+                        let body = self.compile_shared(&node, split);
+                        self.states[split] = State::Split(body, next);
+                        split
+                    }
+                    Some(max) => (min..max).fold(next, |rest, _| {
+                        let body = self.compile_shared(&node, rest);
+                        self.push(State::Split(body, next))
+                    }),
+                };
+                for _ in 0..min {
+                    entry = self.compile_shared(&node, entry);
+                }
+                entry
+            }
+        }
+    }
+
+    // 繰り返しのために同じ構文木を何度も組み立てる
+    fn compile_shared(&mut self, node: &Node, next: usize) -> usize {
+        self.compile(node.duplicate(), next)
+    }
+
+    fn add_state(&self, state: usize, set: &mut Vec<usize>, seen: &mut Vec<bool>) {
+        if seen[state] {
+            return;
+        }
+        seen[state] = true;
+        match self.states[state] {
+            State::Split(a, b) => {
+                self.add_state(a, set, seen);
+                self.add_state(b, set, seen);
+            }
+            State::Jump(next) => self.add_state(next, set, seen),
+            _ => set.push(state),
+        }
+    }
+
+    // tokens[start..] の先頭から最長でマッチする終端
+    fn longest_match(&self, tokens: &[Token], start: usize) -> Option<usize> {
+        let mut current = vec![];
+        let mut seen = vec![false; self.states.len()];
+        self.add_state(self.start, &mut current, &mut seen);
+        let mut longest = None;
+        let mut position = start;
+        loop {
+            if current
+                .iter()
+                .any(|state| matches!(self.states[*state], State::Accept))
+            {
+                longest = Some(position);
+            }
+            if position >= tokens.len() || current.is_empty() {
+                return longest;
+            }
+            let token = &tokens[position];
+            let mut next = vec![];
+            let mut seen = vec![false; self.states.len()];
+            for state in current.iter() {
+                if let State::Token(atom, to) = self.states[*state] {
+                    if self.atoms[atom]
+                        .iter()
+                        .all(|condition| condition.is_match(token))
+                    {
+                        self.add_state(to, &mut next, &mut seen);
+                    }
+                }
+            }
+            current = next;
+            position += 1;
+        }
+    }
+
+    // 左端最長で、重ならないマッチをすべて返す。空のマッチは返さない
+    pub fn find_iter(&self, tokens: &[Token]) -> Vec<PatternMatch> {
+        let mut matches = vec![];
+        let mut start = 0;
+        while start < tokens.len() {
+            match self.longest_match(tokens, start) {
+                Some(end) if end > start => {
+                    matches.push(PatternMatch { start, end });
+                    start = end;
+                }
+                _ => start += 1,
+            }
+        }
+        return matches;
+    }
+
+    pub fn is_match(&self, tokens: &[Token]) -> bool {
+        !self.find_iter(tokens).is_empty()
+    }
+}
+
+impl Node {
+    fn duplicate(&self) -> Node {
+        match self {
+            Node::Atom(conditions) => Node::Atom(
+                conditions
+                    .iter()
+                    .map(|condition| Condition {
+                        field: condition.field,
+                        operator: match &condition.operator {
+                            Operator::Equal(value) => Operator::Equal(value.clone()),
+                            Operator::NotEqual(value) => Operator::NotEqual(value.clone()),
+                            Operator::Regex(regex) => Operator::Regex(regex.clone()),
+                        },
+                    })
+                    .collect(),
+            ),
+            Node::Concat(nodes) => Node::Concat(nodes.iter().map(Node::duplicate).collect()),
+            Node::Alternate(nodes) => Node::Alternate(nodes.iter().map(Node::duplicate).collect()),
+            Node::Repeat { node, min, max } => Node::Repeat {
+                node: Box::new(node.duplicate()),
+                min: *min,
+                max: *max,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter04::answer::Token;
+    use crate::chapter04::pattern::{PatternMatch, TokenPattern};
+
+    fn tokens() -> Vec<Token> {
+        serde_json::from_str(
+            "[{\"surface\":\"彼\",\"base\":\"彼\",\"pos\":\"名詞\",\"pos1\":\"代名詞\"},\
+             {\"surface\":\"の\",\"base\":\"の\",\"pos\":\"助詞\",\"pos1\":\"連体化\"},\
+             {\"surface\":\"猫\",\"base\":\"猫\",\"pos\":\"名詞\",\"pos1\":\"一般\"},\
+             {\"surface\":\"の\",\"base\":\"の\",\"pos\":\"助詞\",\"pos1\":\"連体化\"},\
+             {\"surface\":\"額\",\"base\":\"額\",\"pos\":\"名詞\",\"pos1\":\"一般\"},\
+             {\"surface\":\"は\",\"base\":\"は\",\"pos\":\"助詞\",\"pos1\":\"係助詞\"},\
+             {\"surface\":\"人間\",\"base\":\"人間\",\"pos\":\"名詞\",\"pos1\":\"一般\"},\
+             {\"surface\":\"社会\",\"base\":\"社会\",\"pos\":\"名詞\",\"pos1\":\"一般\"},\
+             {\"surface\":\"問題\",\"base\":\"問題\",\"pos\":\"名詞\",\"pos1\":\"一般\"},\
+             {\"surface\":\"に\",\"base\":\"に\",\"pos\":\"助詞\",\"pos1\":\"格助詞\"},\
+             {\"surface\":\"なっ\",\"base\":\"なる\",\"pos\":\"動詞\",\"pos1\":\"自立\"},\
+             {\"surface\":\"た\",\"base\":\"た\",\"pos\":\"助動詞\",\"pos1\":\"\"}]",
+        )
+        .unwrap()
+    }
+
+    fn surfaces(pattern: &str) -> Vec<String> {
+        let tokens = tokens();
+        TokenPattern::new(pattern)
+            .unwrap()
+            .find_iter(&tokens)
+            .iter()
+            .map(|m| m.surface(&tokens))
+            .collect()
+    }
+
+    #[test]
+    pub fn success_find_sequence() {
+        let tokens = tokens();
+        let pattern = TokenPattern::new("[pos=名詞] [surface=の] [pos=名詞]").unwrap();
+        assert_eq!(
+            vec![PatternMatch { start: 0, end: 3 }],
+            pattern.find_iter(&tokens)
+        );
+        assert_eq!(
+            vec!["猫の額"],
+            surfaces("[pos=名詞 & pos1!=代名詞] [surface=の] [pos=名詞]")
+        );
+        assert_eq!(vec!["なった"], surfaces("[base~^(する|なる)$] []"));
+        assert_eq!(vec!["は"], surfaces("[surface=\"は\"]"));
+    }
+
+    #[test]
+    pub fn success_find_with_quantifiers() {
+        assert_eq!(vec!["人間社会問題"], surfaces("[pos=名詞] [pos=名詞]+"));
+        assert_eq!(vec!["人間社会問題"], surfaces("[pos=名詞]{2,}"));
+        assert_eq!(vec!["人間社会"], surfaces("[pos=名詞]{2}"));
+        assert_eq!(
+            vec!["彼の猫の額", "人間社会問題"],
+            surfaces("[pos=名詞] ([surface=の] [pos=名詞])* [pos=名詞]*")
+        );
+        assert_eq!(
+            vec!["社会問題に", "なった"],
+            surfaces("[surface=社会]? [surface=問題] [pos=助詞] | [pos=動詞] [pos=助動詞]?")
+        );
+    }
+
+    #[test]
+    pub fn success_pattern_errors() {
+        assert!(TokenPattern::new("").is_err());
+        assert!(TokenPattern::new("[pos=名詞").is_err());
+        assert!(TokenPattern::new("[color=赤]").is_err());
+        assert!(TokenPattern::new("[pos=名詞]{3,1}").is_err());
+        assert!(TokenPattern::new("[surface~(]").is_err());
+        let error = TokenPattern::new("[pos=名詞] x").unwrap_err();
+        assert_eq!(9, error.position);
+    }
+}
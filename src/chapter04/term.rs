@@ -0,0 +1,165 @@
+use crate::chapter04::answer::Token;
+use std::collections::HashSet;
+use std::io::BufRead;
+use std::str::FromStr;
+
+// トークンを数えるときの語の見出し
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TermKey {
+    Surface,
+    Base,
+    // 「原形/品詞」
+    BasePos,
+    Pos,
+}
+
+impl TermKey {
+    pub fn key(&self, token: &Token) -> String {
+        match self {
+            TermKey::Surface => token.surface().to_string(),
+            TermKey::Base => base_or_surface(token).to_string(),
+            TermKey::BasePos => format!("{}/{}", base_or_surface(token), token.pos()),
+            TermKey::Pos => token.pos().to_string(),
+        }
+    }
+}
+
+impl Default for TermKey {
+    fn default() -> Self {
+        TermKey::Surface
+    }
+}
+
+impl FromStr for TermKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "surface" => Ok(TermKey::Surface),
+            "base" => Ok(TermKey::Base),
+            "base/pos" => Ok(TermKey::BasePos),
+            "pos" => Ok(TermKey::Pos),
+            _ => Err(format!("unsupported term key: {}", s)),
+        }
+    }
+}
+
+// 未知語などで原形が無いときは表層形を使う
+fn base_or_surface(token: &Token) -> &str {
+    match token.base() {
+        "" | "*" => token.surface(),
+        base => base,
+    }
+}
+
+// 数える対象のトークンを選ぶ（ストップワードと品詞）
+#[derive(Clone, Debug, Default)]
+pub struct TokenSelector {
+    stopwords: HashSet<String>,
+    pos: HashSet<String>,
+    excluded_pos: HashSet<String>,
+}
+
+impl TokenSelector {
+    pub fn new() -> TokenSelector {
+        TokenSelector::default()
+    }
+
+    // 名詞、動詞、形容詞、副詞だけを対象にする
+    pub fn content_words() -> TokenSelector {
+        TokenSelector::new().with_pos(&["名詞", "動詞", "形容詞", "副詞"])
+    }
+
+    // 表層形か原形が一致するトークンを除く
+    pub fn with_stopwords<S: AsRef<str>>(mut self, stopwords: &[S]) -> TokenSelector {
+        self.stopwords
+            .extend(stopwords.iter().map(|word| word.as_ref().to_string()));
+        return self;
+    }
+
+    // 指定した品詞のトークンだけを対象にする
+    pub fn with_pos<S: AsRef<str>>(mut self, pos: &[S]) -> TokenSelector {
+        self.pos
+            .extend(pos.iter().map(|pos| pos.as_ref().to_string()));
+        return self;
+    }
+
+    // 品詞か「品詞,品詞細分類1」が一致するトークンを除く
+    pub fn without_pos<S: AsRef<str>>(mut self, pos: &[S]) -> TokenSelector {
+        self.excluded_pos
+            .extend(pos.iter().map(|pos| pos.as_ref().to_string()));
+        return self;
+    }
+
+    pub fn is_target(&self, token: &Token) -> bool {
+        if self.stopwords.contains(token.surface())
+            || self.stopwords.contains(base_or_surface(token))
+        {
+            return false;
+        }
+        if !self.pos.is_empty() && !self.pos.contains(token.pos()) {
+            return false;
+        }
+        return !self.excluded_pos.contains(token.pos())
+            && !self
+                .excluded_pos
+                .contains(&format!("{},{}", token.pos(), token.pos1()));
+    }
+}
+
+// 1行1語のストップワード。空行と # から始まる行は読み飛ばす
+pub fn load_stopwords<R: BufRead>(input: R) -> Vec<String> {
+    input
+        .lines()
+        .filter_map(|line| line.ok())
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter04::term::{load_stopwords, TermKey, TokenSelector};
+    use crate::test_support::tokens;
+    use std::io::Cursor;
+
+    #[test]
+    pub fn success_term_key_and_selector() {
+        let tokens = tokens(
+            "猫/猫/名詞/一般 が/が/助詞/格助詞 い/いる/動詞/自立 \
+             ニャー/*/名詞/一般 。/。/記号/句点",
+        );
+        assert_eq!("い", TermKey::Surface.key(&tokens[2]));
+        assert_eq!("いる", TermKey::Base.key(&tokens[2]));
+        assert_eq!("ニャー", TermKey::Base.key(&tokens[3]));
+        assert_eq!("いる/動詞", TermKey::BasePos.key(&tokens[2]));
+        assert_eq!("記号", TermKey::Pos.key(&tokens[4]));
+        assert_eq!(Ok(TermKey::BasePos), "base/pos".parse());
+        assert_eq!(Ok(TermKey::Base), "base".parse());
+        assert!("lemma".parse::<TermKey>().is_err());
+
+        let selected = |selector: TokenSelector| -> Vec<&str> {
+            tokens
+                .iter()
+                .filter(|token| selector.is_target(token))
+                .map(|token| token.surface())
+                .collect()
+        };
+        assert_eq!(5, selected(TokenSelector::new()).len());
+        assert_eq!(
+            vec!["猫", "い", "ニャー"],
+            selected(TokenSelector::content_words())
+        );
+        assert_eq!(
+            vec!["猫", "ニャー"],
+            selected(TokenSelector::content_words().with_stopwords(&["いる"]))
+        );
+        assert_eq!(
+            vec!["猫", "い", "ニャー"],
+            selected(TokenSelector::new().without_pos(&["助詞", "記号,句点"]))
+        );
+
+        let stopwords = load_stopwords(Cursor::new("# 助詞\nの\n\n が \n"));
+        assert_eq!(vec!["の", "が"], stopwords);
+    }
+}
@@ -0,0 +1,222 @@
+use crate::chapter04::answer::Token;
+use crate::chapter04::command::Command;
+use crate::chapter04::term::{TermKey, TokenSelector};
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+use std::str::FromStr;
+
+// TF-IDF の文書の単位
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Document {
+    // 解析結果の1行（1文）
+    Line,
+    // 「一」「二」…の見出しで区切った章
+    Chapter,
+}
+
+impl FromStr for Document {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "line" => Ok(Document::Line),
+            "chapter" => Ok(Document::Chapter),
+            _ => Err(format!("unsupported document unit: {}", s)),
+        }
+    }
+}
+
+// 漢数字だけの文を章の見出しとみなす
+fn is_chapter_heading(tokens: &Vec<Token>) -> bool {
+    !tokens.is_empty()
+        && tokens
+            .iter()
+            .flat_map(|token| token.surface().chars())
+            .all(|c| "〇一二三四五六七八九十".contains(c))
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TermScore<'a> {
+    pub term: &'a str,
+    pub tf: f64,
+    pub idf: f64,
+    pub tf_idf: f64,
+}
+
+// 文書ごとの語の出現回数を数えて TF-IDF を求める
+//
+// tf = 文書中の出現回数 / 文書中の語数、idf = ln((1 + 文書数) / (1 + 文書頻度)) + 1
+#[derive(Debug)]
+pub struct TfIdf {
+    key: TermKey,
+    selector: TokenSelector,
+    unit: Document,
+    documents: Vec<BTreeMap<String, u32>>,
+    document_frequency: BTreeMap<String, usize>,
+}
+
+impl TfIdf {
+    pub fn new(unit: Document) -> TfIdf {
+        TfIdf {
+            key: TermKey::default(),
+            selector: TokenSelector::default(),
+            unit,
+            documents: vec![],
+            document_frequency: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_key(mut self, key: TermKey) -> TfIdf {
+        self.key = key;
+        return self;
+    }
+
+    pub fn with_selector(mut self, selector: TokenSelector) -> TfIdf {
+        self.selector = selector;
+        return self;
+    }
+
+    pub fn document_count(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn document_frequency(&self, term: &str) -> usize {
+        *self.document_frequency.get(term).unwrap_or(&0)
+    }
+
+    pub fn idf(&self, term: &str) -> f64 {
+        let n = self.documents.len() as f64;
+        ((1.0 + n) / (1.0 + self.document_frequency(term) as f64)).ln() + 1.0
+    }
+
+    // index 番目の文書の語を TF-IDF の降順（同点なら語の順）に並べる
+    pub fn scores(&self, index: usize) -> Vec<TermScore<'_>> {
+        let document = &self.documents[index];
+        let total: u32 = document.values().sum();
+        let mut scores: Vec<TermScore> = document
+            .iter()
+            .map(|(term, count)| {
+                let tf = *count as f64 / total as f64;
+                let idf = self.idf(term);
+                TermScore {
+                    term: term.as_str(),
+                    tf,
+                    idf,
+                    tf_idf: tf * idf,
+                }
+            })
+            .collect();
+        scores.sort_by(|x, y| {
+            y.tf_idf
+                .partial_cmp(&x.tf_idf)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| x.term.cmp(y.term))
+        });
+        return scores;
+    }
+
+    // 文書ごとに上位 n 語を書き出す
+    pub fn write_top<W: Write>(&self, out: &mut W, n: usize) -> io::Result<()> {
+        writeln!(out, "document\tterm\ttf\tidf\ttf_idf")?;
+        for index in 0..self.documents.len() {
+            for score in self.scores(index).iter().take(n) {
+                writeln!(
+                    out,
+                    "{}\t{}\t{:.6}\t{:.6}\t{:.6}",
+                    index + 1,
+                    score.term,
+                    score.tf,
+                    score.idf,
+                    score.tf_idf
+                )?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl Command for TfIdf {
+    fn execute(&mut self, tokens: &Vec<Token>) {
+        match self.unit {
+            Document::Line => self.documents.push(BTreeMap::new()),
+            Document::Chapter if is_chapter_heading(tokens) => {
+                self.documents.push(BTreeMap::new());
+                return;
+            }
+            // 最初の見出しより前の文は1つの文書にまとめる
+            Document::Chapter if self.documents.is_empty() => self.documents.push(BTreeMap::new()),
+            Document::Chapter => {}
+        }
+        let mut terms = vec![];
+        for token in tokens.iter().filter(|token| self.selector.is_target(token)) {
+            terms.push(self.key.key(token));
+        }
+        let document = self.documents.last_mut().expect("no document");
+        for term in terms {
+            let count = document.entry(term.to_string()).or_insert(0);
+            if *count == 0 {
+                *self.document_frequency.entry(term).or_insert(0) += 1;
+            }
+            *count += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter04::command::load_json;
+    use crate::chapter04::term::{TermKey, TokenSelector};
+    use crate::chapter04::tf_idf::{Document, TfIdf};
+    use crate::test_support::sentences_json;
+    use std::io::Cursor;
+
+    fn novel() -> String {
+        sentences_json(&[
+            "一//名詞",
+            "猫//名詞 が//助詞 いる//動詞",
+            "猫//名詞 が//助詞 鳴く//動詞",
+            "二//名詞",
+            "犬//名詞 が//助詞 いる//動詞",
+        ])
+    }
+
+    #[test]
+    pub fn success_tf_idf_by_chapter() {
+        let mut cmd = TfIdf::new(Document::Chapter)
+            .with_key(TermKey::Base)
+            .with_selector(TokenSelector::content_words());
+        load_json(Cursor::new(novel()), &mut cmd);
+        assert_eq!(2, cmd.document_count());
+        assert_eq!(2, cmd.document_frequency("いる"));
+        assert_eq!(1, cmd.document_frequency("猫"));
+        assert_eq!(0, cmd.document_frequency("が"));
+        assert_eq!(1.0, cmd.idf("いる"));
+        let scores = cmd.scores(0);
+        assert_eq!(
+            vec!["猫", "鳴く", "いる"],
+            scores.iter().map(|score| score.term).collect::<Vec<&str>>()
+        );
+        assert_eq!(0.5, scores[0].tf);
+        assert!((scores[0].idf - (1.5f64).ln() - 1.0).abs() < 1.0e-9);
+
+        let mut out: Vec<u8> = vec![];
+        cmd.write_top(&mut out, 1).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(3, lines.len());
+        assert!(lines[1].starts_with("1\t猫\t0.500000\t"));
+        assert!(lines[2].starts_with("2\t犬\t0.500000\t"));
+    }
+
+    #[test]
+    pub fn success_tf_idf_by_line() {
+        let mut cmd = TfIdf::new(Document::Line).with_key(TermKey::Pos);
+        load_json(Cursor::new(novel()), &mut cmd);
+        assert_eq!(5, cmd.document_count());
+        assert_eq!(5, cmd.document_frequency("名詞"));
+        assert_eq!(3, cmd.document_frequency("助詞"));
+        assert_eq!(Ok(Document::Chapter), "chapter".parse());
+        assert!("paragraph".parse::<Document>().is_err());
+    }
+}
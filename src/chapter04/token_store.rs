@@ -0,0 +1,418 @@
+use crate::chapter04::answer::Token;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::io::Write;
+use std::str;
+
+// 形態素解析結果のバイナリ形式
+//
+//   "NKTS" | version: u8
+//   文字列表: varint 件数, (varint バイト長, UTF-8)*
+//   文の数: varint, 文のオフセット: u32 LE * (文の数 + 1)  ※トークン領域の先頭からのバイト位置
+//   トークン領域: 文ごとに varint 文ID, varint トークン数,
+//     トークンごとに varint 文字列ID * 10, varint start, varint (end - start), u8 未知語フラグ
+const MAGIC: &[u8; 4] = b"NKTS";
+const VERSION: u8 = 1;
+const STRING_FIELDS: usize = 10;
+
+#[derive(Debug)]
+pub enum TokenStoreError {
+    Io(io::Error),
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    InvalidUtf8,
+    InvalidStringId(usize),
+    SentenceOutOfRange(usize),
+    // トークン領域がオフセット（u32）で表せる大きさを超えた
+    TooLarge,
+    // 書き込もうとしたトークンの end が start より前にある
+    InvalidSpan { start: usize, end: usize },
+    // 読み出した start + 長さが usize に収まらない
+    SpanOverflow,
+    InvalidJson { line: usize, message: String },
+}
+
+impl fmt::Display for TokenStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenStoreError::Io(error) => write!(f, "Error occurred... {}", error),
+            TokenStoreError::InvalidMagic => write!(f, "Not a token store."),
+            TokenStoreError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported token store version {}.", version)
+            }
+            TokenStoreError::Truncated => write!(f, "Token store is truncated."),
+            TokenStoreError::InvalidUtf8 => write!(f, "String table is not valid UTF-8."),
+            TokenStoreError::InvalidStringId(id) => write!(f, "Unknown string id {}.", id),
+            TokenStoreError::SentenceOutOfRange(index) => {
+                write!(f, "Sentence {} is out of range.", index)
+            }
+            TokenStoreError::TooLarge => write!(f, "Token store exceeds 4 GiB."),
+            TokenStoreError::InvalidSpan { start, end } => {
+                write!(f, "Token span {}..{} ends before it starts.", start, end)
+            }
+            TokenStoreError::SpanOverflow => write!(f, "Token span overflows."),
+            TokenStoreError::InvalidJson { line, message } => {
+                write!(f, "Invalid JSON at line {}. {}", line, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for TokenStoreError {}
+
+impl From<io::Error> for TokenStoreError {
+    fn from(error: io::Error) -> Self {
+        TokenStoreError::Io(error)
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+// bytes[*position..] から varint を読み、position を進める
+fn read_varint(bytes: &[u8], position: &mut usize) -> Result<u64, TokenStoreError> {
+    let mut value: u64 = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *bytes.get(*position).ok_or(TokenStoreError::Truncated)?;
+        *position += 1;
+        if shift >= 64 {
+            return Err(TokenStoreError::Truncated);
+        }
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+// オフセットを u32 LE で書く。収まらなければ TooLarge
+fn write_offsets(buf: &mut Vec<u8>, offsets: &[usize]) -> Result<(), TokenStoreError> {
+    for offset in offsets.iter() {
+        let offset = u32::try_from(*offset).map_err(|_| TokenStoreError::TooLarge)?;
+        buf.extend_from_slice(&offset.to_le_bytes());
+    }
+    return Ok(());
+}
+
+// 文を溜めて、finish で文字列表と一緒に書き出す
+#[derive(Debug, Default)]
+pub struct TokenStoreWriter {
+    ids: HashMap<String, u64>,
+    strings: Vec<String>,
+    // u32 に収まるかは finish で確かめる
+    offsets: Vec<usize>,
+    tokens: Vec<u8>,
+}
+
+impl TokenStoreWriter {
+    pub fn new() -> TokenStoreWriter {
+        TokenStoreWriter::default()
+    }
+
+    fn intern(&mut self, value: &str) -> u64 {
+        if let Some(id) = self.ids.get(value) {
+            return *id;
+        }
+        let id = self.strings.len() as u64;
+        self.strings.push(value.to_string());
+        self.ids.insert(value.to_string(), id);
+        return id;
+    }
+
+    // 不正な範囲のトークンがあれば何も書かずに InvalidSpan を返す
+    pub fn push_sentence(&mut self, tokens: &[Token]) -> Result<(), TokenStoreError> {
+        if let Some(token) = tokens.iter().find(|token| token.end() < token.start()) {
+            return Err(TokenStoreError::InvalidSpan {
+                start: token.start(),
+                end: token.end(),
+            });
+        }
+        self.offsets.push(self.tokens.len());
+        let sentence_id = tokens.first().map(|token| token.sentence_id()).unwrap_or(0);
+        write_varint(&mut self.tokens, sentence_id as u64);
+        write_varint(&mut self.tokens, tokens.len() as u64);
+        for token in tokens {
+            let fields: [&str; STRING_FIELDS] = [
+                token.surface(),
+                token.base(),
+                token.pos(),
+                token.pos1(),
+                token.pos2(),
+                token.pos3(),
+                token.conjugation_type(),
+                token.conjugation_form(),
+                token.reading(),
+                token.pronunciation(),
+            ];
+            for field in fields.iter() {
+                let id = self.intern(field);
+                write_varint(&mut self.tokens, id);
+            }
+            write_varint(&mut self.tokens, token.start() as u64);
+            write_varint(&mut self.tokens, (token.end() - token.start()) as u64);
+            self.tokens.push(token.is_unknown() as u8);
+        }
+        return Ok(());
+    }
+
+    pub fn sentence_count(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn finish<W: Write>(mut self, out: &mut W) -> Result<(), TokenStoreError> {
+        self.offsets.push(self.tokens.len());
+        let mut header = vec![];
+        header.extend_from_slice(MAGIC);
+        header.push(VERSION);
+        write_varint(&mut header, self.strings.len() as u64);
+        for value in self.strings.iter() {
+            write_varint(&mut header, value.len() as u64);
+            header.extend_from_slice(value.as_bytes());
+        }
+        write_varint(&mut header, (self.offsets.len() - 1) as u64);
+        write_offsets(&mut header, &self.offsets)?;
+        out.write_all(&header)?;
+        out.write_all(&self.tokens)?;
+        out.flush()?;
+        return Ok(());
+    }
+}
+
+// バッファを借用したまま読むトークン（文字列はコピーしない）。JSON は Token と同じ形になる
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TokenRef<'a> {
+    pub surface: &'a str,
+    pub base: &'a str,
+    pub pos: &'a str,
+    pub pos1: &'a str,
+    pub pos2: &'a str,
+    pub pos3: &'a str,
+    pub conjugation_type: &'a str,
+    pub conjugation_form: &'a str,
+    pub reading: &'a str,
+    pub pronunciation: &'a str,
+    pub start: usize,
+    pub end: usize,
+    pub is_unknown: bool,
+    pub sentence_id: usize,
+}
+
+#[derive(Debug)]
+pub struct TokenStore<'a> {
+    strings: Vec<&'a str>,
+    offsets: Vec<u32>,
+    tokens: &'a [u8],
+}
+
+impl<'a> TokenStore<'a> {
+    pub fn open(bytes: &'a [u8]) -> Result<TokenStore<'a>, TokenStoreError> {
+        if bytes.len() < MAGIC.len() + 1 || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(TokenStoreError::InvalidMagic);
+        }
+        let version = bytes[MAGIC.len()];
+        if version != VERSION {
+            return Err(TokenStoreError::UnsupportedVersion(version));
+        }
+        let mut position = MAGIC.len() + 1;
+        let string_count = read_varint(bytes, &mut position)? as usize;
+        let mut strings = Vec::with_capacity(string_count.min(bytes.len()));
+        for _ in 0..string_count {
+            let length = read_varint(bytes, &mut position)? as usize;
+            let end = position
+                .checked_add(length)
+                .filter(|end| *end <= bytes.len())
+                .ok_or(TokenStoreError::Truncated)?;
+            let value =
+                str::from_utf8(&bytes[position..end]).map_err(|_| TokenStoreError::InvalidUtf8)?;
+            strings.push(value);
+            position = end;
+        }
+        let sentence_count = read_varint(bytes, &mut position)? as usize;
+        let offsets_end = sentence_count
+            .checked_add(1)
+            .and_then(|n| n.checked_mul(4))
+            .and_then(|size| position.checked_add(size))
+            .filter(|end| *end <= bytes.len())
+            .ok_or(TokenStoreError::Truncated)?;
+        let offsets: Vec<u32> = bytes[position..offsets_end]
+            .chunks(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        let tokens = &bytes[offsets_end..];
+        if offsets.last().map(|end| *end as usize) != Some(tokens.len()) {
+            return Err(TokenStoreError::Truncated);
+        }
+        return Ok(TokenStore {
+            strings,
+            offsets,
+            tokens,
+        });
+    }
+
+    pub fn sentence_count(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn string_count(&self) -> usize {
+        self.strings.len()
+    }
+
+    // index 番目の文のトークン列
+    pub fn sentence(&self, index: usize) -> Result<Vec<TokenRef<'a>>, TokenStoreError> {
+        let (start, end) = match (self.offsets.get(index), self.offsets.get(index + 1)) {
+            (Some(start), Some(end)) => (*start as usize, *end as usize),
+            _ => return Err(TokenStoreError::SentenceOutOfRange(index)),
+        };
+        if start > end || end > self.tokens.len() {
+            return Err(TokenStoreError::Truncated);
+        }
+        let bytes = &self.tokens[..end];
+        let mut position = start;
+        let sentence_id = read_varint(bytes, &mut position)? as usize;
+        let token_count = read_varint(bytes, &mut position)? as usize;
+        let mut tokens = Vec::with_capacity(token_count.min(end - start));
+        for _ in 0..token_count {
+            let mut fields = [""; STRING_FIELDS];
+            for field in fields.iter_mut() {
+                let id = read_varint(bytes, &mut position)? as usize;
+                *field = self
+                    .strings
+                    .get(id)
+                    .ok_or(TokenStoreError::InvalidStringId(id))?;
+            }
+            let token_start = read_varint(bytes, &mut position)? as usize;
+            let length = read_varint(bytes, &mut position)? as usize;
+            let flags = *bytes.get(position).ok_or(TokenStoreError::Truncated)?;
+            position += 1;
+            tokens.push(TokenRef {
+                surface: fields[0],
+                base: fields[1],
+                pos: fields[2],
+                pos1: fields[3],
+                pos2: fields[4],
+                pos3: fields[5],
+                conjugation_type: fields[6],
+                conjugation_form: fields[7],
+                reading: fields[8],
+                pronunciation: fields[9],
+                start: token_start,
+                end: token_start
+                    .checked_add(length)
+                    .ok_or(TokenStoreError::SpanOverflow)?,
+                is_unknown: flags & 1 == 1,
+                sentence_id,
+            });
+        }
+        return Ok(tokens);
+    }
+
+    pub fn sentences(
+        &self,
+    ) -> impl Iterator<Item = Result<Vec<TokenRef<'a>>, TokenStoreError>> + '_ {
+        (0..self.sentence_count()).map(move |index| self.sentence(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter04::token_store::{
+        read_varint, write_offsets, write_varint, TokenStore, TokenStoreError, TokenStoreWriter,
+    };
+
+    #[test]
+    pub fn success_varint() {
+        let values = [
+            0,
+            1,
+            127,
+            128,
+            300,
+            16384,
+            u32::max_value() as u64,
+            u64::max_value(),
+        ];
+        let mut buf = vec![];
+        values
+            .iter()
+            .for_each(|value| write_varint(&mut buf, *value));
+        let mut position = 0;
+        for value in values.iter() {
+            assert_eq!(*value, read_varint(&buf, &mut position).unwrap());
+        }
+        assert_eq!(buf.len(), position);
+        assert!(read_varint(&[0x80], &mut 0).is_err());
+    }
+
+    #[test]
+    pub fn success_open_invalid_store() {
+        match TokenStore::open(b"JSON") {
+            Err(TokenStoreError::InvalidMagic) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        match TokenStore::open(b"NKTS\x02") {
+            Err(TokenStoreError::UnsupportedVersion(2)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+        match TokenStore::open(b"NKTS\x01\x01\x05ab") {
+            Err(TokenStoreError::Truncated) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn success_write_offsets() {
+        let mut buf = vec![];
+        write_offsets(&mut buf, &[0, 1, u32::max_value() as usize]).unwrap();
+        assert_eq!(vec![0, 0, 0, 0, 1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff], buf);
+        if let Some(too_large) = (u32::max_value() as usize).checked_add(1) {
+            match write_offsets(&mut vec![], &[too_large]) {
+                Err(TokenStoreError::TooLarge) => {}
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    pub fn success_sentence_out_of_range() {
+        let mut bytes = vec![];
+        let mut writer = TokenStoreWriter::new();
+        writer.push_sentence(&[]).unwrap();
+        writer.finish(&mut bytes).unwrap();
+        let store = TokenStore::open(&bytes).unwrap();
+        assert_eq!(1, store.sentence_count());
+        assert!(store.sentence(0).unwrap().is_empty());
+        match store.sentence(1) {
+            Err(TokenStoreError::SentenceOutOfRange(1)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn success_span_overflow() {
+        // 空文字列1つの文字列表と、start と長さがどちらも u64::max_value() のトークン1つの文
+        let mut tokens = vec![0, 1];
+        tokens.extend_from_slice(&[0; 10]);
+        write_varint(&mut tokens, u64::max_value());
+        write_varint(&mut tokens, u64::max_value());
+        tokens.push(0);
+        let mut bytes = b"NKTS\x01\x01\x00\x01".to_vec();
+        write_offsets(&mut bytes, &[0, tokens.len()]).unwrap();
+        bytes.extend(tokens);
+        let store = TokenStore::open(&bytes).unwrap();
+        match store.sentence(0) {
+            Err(TokenStoreError::SpanOverflow) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+
+// 出現頻度の順位
+#[derive(Debug, PartialEq)]
+pub struct RankedTerm<'a> {
+    pub rank: usize,
+    pub term: &'a str,
+    pub frequency: u32,
+}
+
+// log10(頻度) = intercept - exponent * log10(順位) を最小二乗法で当てはめた結果
+#[derive(Debug, PartialEq)]
+pub struct ZipfFit {
+    pub exponent: f64,
+    pub intercept: f64,
+    // 決定係数
+    pub r_squared: f64,
+    // log10 空間での残差の二乗平均平方根
+    pub rmse: f64,
+    pub points: usize,
+}
+
+impl ZipfFit {
+    pub fn predict(&self, rank: usize) -> f64 {
+        10f64.powf(self.intercept - self.exponent * (rank as f64).log10())
+    }
+}
+
+// 頻度の降順（同じ頻度なら語の順）に順位を付ける
+pub fn rank_frequency(counts: &BTreeMap<String, u32>) -> Vec<RankedTerm<'_>> {
+    let mut terms: Vec<(&String, &u32)> = counts.iter().collect();
+    terms.sort_by(|x, y| y.1.cmp(x.1).then_with(|| x.0.cmp(y.0)));
+    return terms
+        .into_iter()
+        .enumerate()
+        .map(|(i, (term, frequency))| RankedTerm {
+            rank: i + 1,
+            term: term.as_str(),
+            frequency: *frequency,
+        })
+        .collect();
+}
+
+// 順位 from..=to の範囲で当てはめる。点が2つ未満なら None
+pub fn fit_zipf(ranking: &[RankedTerm], from: usize, to: usize) -> Option<ZipfFit> {
+    let points: Vec<(f64, f64)> = ranking
+        .iter()
+        .filter(|term| from <= term.rank && term.rank <= to && term.frequency > 0)
+        .map(|term| ((term.rank as f64).log10(), (term.frequency as f64).log10()))
+        .collect();
+    let n = points.len() as f64;
+    if points.len() < 2 {
+        return None;
+    }
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let syy: f64 = points.iter().map(|(_, y)| (y - mean_y).powi(2)).sum();
+    if sxx == 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+    let ss_residual: f64 = points
+        .iter()
+        .map(|(x, y)| (y - (intercept + slope * x)).powi(2))
+        .sum();
+    let r_squared = if syy == 0.0 {
+        1.0
+    } else {
+        1.0 - ss_residual / syy
+    };
+    return Some(ZipfFit {
+        exponent: -slope,
+        intercept,
+        r_squared,
+        rmse: (ss_residual / n).sqrt(),
+        points: points.len(),
+    });
+}
+
+// 頻度ごとの語の数（頻度 1 の語がいくつあるか、など）
+pub fn frequency_of_frequencies(ranking: &[RankedTerm]) -> BTreeMap<u32, usize> {
+    let mut histogram = BTreeMap::new();
+    ranking
+        .iter()
+        .for_each(|term| *histogram.entry(term.frequency).or_insert(0) += 1);
+    return histogram;
+}
+
+// プロット用: 順位, 語, 頻度, log10(順位), log10(頻度), 当てはめた頻度
+pub fn write_rank_frequency<W: Write>(
+    out: &mut W,
+    ranking: &[RankedTerm],
+    fit: Option<&ZipfFit>,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "rank\tterm\tfrequency\tlog10_rank\tlog10_frequency\tfitted_frequency"
+    )?;
+    for term in ranking {
+        let fitted = fit
+            .map(|fit| format!("{:.4}", fit.predict(term.rank)))
+            .unwrap_or_default();
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{:.6}\t{:.6}\t{}",
+            term.rank,
+            term.term,
+            term.frequency,
+            (term.rank as f64).log10(),
+            (term.frequency as f64).log10(),
+            fitted
+        )?;
+    }
+    return Ok(());
+}
+
+pub fn write_frequency_of_frequencies<W: Write>(
+    out: &mut W,
+    histogram: &BTreeMap<u32, usize>,
+) -> io::Result<()> {
+    writeln!(out, "frequency\tterms")?;
+    for (frequency, terms) in histogram {
+        writeln!(out, "{}\t{}", frequency, terms)?;
+    }
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter04::zipf::{
+        fit_zipf, frequency_of_frequencies, rank_frequency, write_frequency_of_frequencies,
+        write_rank_frequency, RankedTerm,
+    };
+    use std::collections::BTreeMap;
+
+    fn counts(pairs: &[(&str, u32)]) -> BTreeMap<String, u32> {
+        pairs
+            .iter()
+            .map(|(term, count)| (term.to_string(), *count))
+            .collect()
+    }
+
+    #[test]
+    pub fn success_rank_frequency() {
+        let counts = counts(&[("の", 10), ("猫", 3), ("犬", 3), ("。", 5)]);
+        let ranking = rank_frequency(&counts);
+        assert_eq!(
+            vec![
+                RankedTerm {
+                    rank: 1,
+                    term: "の",
+                    frequency: 10
+                },
+                RankedTerm {
+                    rank: 2,
+                    term: "。",
+                    frequency: 5
+                },
+                RankedTerm {
+                    rank: 3,
+                    term: "犬",
+                    frequency: 3
+                },
+                RankedTerm {
+                    rank: 4,
+                    term: "猫",
+                    frequency: 3
+                },
+            ],
+            ranking
+        );
+        let histogram = frequency_of_frequencies(&ranking);
+        assert_eq!(Some(&2), histogram.get(&3));
+        let mut out: Vec<u8> = vec![];
+        write_frequency_of_frequencies(&mut out, &histogram).unwrap();
+        assert_eq!(
+            "frequency\tterms\n3\t2\n5\t1\n10\t1\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn success_fit_zipf() {
+        // 頻度 = 1000 / 順位 なら指数は 1
+        let pairs: Vec<(String, u32)> = (1..=50)
+            .map(|rank| {
+                (
+                    format!("w{:02}", rank),
+                    (1000.0 / rank as f64).round() as u32,
+                )
+            })
+            .collect();
+        let counts: BTreeMap<String, u32> = pairs.into_iter().collect();
+        let ranking = rank_frequency(&counts);
+        let fit = fit_zipf(&ranking, 1, 50).unwrap();
+        assert!((fit.exponent - 1.0).abs() < 0.01, "{:?}", fit);
+        assert!((fit.intercept - 3.0).abs() < 0.01, "{:?}", fit);
+        assert!(fit.r_squared > 0.999);
+        assert_eq!(50, fit.points);
+        assert!((fit.predict(10) - 100.0).abs() < 2.0);
+        assert_eq!(10, fit_zipf(&ranking, 1, 10).unwrap().points);
+        assert!(fit_zipf(&ranking, 51, 100).is_none());
+
+        let mut out: Vec<u8> = vec![];
+        write_rank_frequency(&mut out, &ranking[..2], Some(&fit)).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(3, lines.len());
+        assert!(lines[1].starts_with("1\tw01\t1000\t0.000000\t3.000000\t"));
+    }
+}
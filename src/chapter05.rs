@@ -0,0 +1,6 @@
+pub mod answer;
+pub mod cabocha;
+pub mod case_frame;
+pub mod parser;
+pub mod path;
+pub mod tree;
@@ -0,0 +1,613 @@
+use crate::chapter03::answer::{extract_sections, load_json};
+use crate::chapter05::cabocha::{
+    read_sentences, read_sentences_from_zip, read_zip_entry, write_sentence, CabochaError,
+};
+use crate::chapter05::case_frame::{CaseFrame, CaseFrameExtractor, CaseFrameStatistics};
+use crate::chapter05::parser::{DependencyParser, PARSER_VERSION};
+use crate::chapter05::path::{noun_pair_paths, noun_paths_to_root};
+use crate::chapter05::tree::Tree;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use zip::result::ZipError;
+
+const AI_ZIP_PATH: &str = "./data/chap05/ai.ja.zip";
+const AI_PARSED_PATH: &str = "./data/chap05/ai.ja.txt.parsed";
+const AI_PARSED_ENTRY: &str = "ai.ja.txt.parsed";
+const AI_TEXT_ENTRY: &str = "ai.ja.txt";
+
+// 組み込みの係り受け解析器で ai.ja.txt を解析した結果。CaboCha の結果とは分け、
+// 解析器の版ごとに別のファイルにして、古い解析器の結果を読まないようにする
+fn ai_lindera_parsed_path() -> String {
+    format!("./data/chap05/ai.ja.txt.lindera-v{}.parsed", PARSER_VERSION)
+}
+
+// 解析結果が zip より新しければ使える。zip が差し替えられていたら解析し直す
+fn is_fresh_cache(path: &str) -> bool {
+    let modified = |path: &str| fs::metadata(path).and_then(|metadata| metadata.modified());
+    match (modified(path), modified(AI_ZIP_PATH)) {
+        (Ok(cache), Ok(zip)) => cache >= zip,
+        (Ok(_), Err(_)) => true,
+        (Err(_), _) => false,
+    }
+}
+
+// ch05-40. 係り受け解析結果の読み込み（形態素）
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Morph {
+    pub surface: String,
+    pub base: String,
+    pub pos: String,
+    pub pos1: String,
+}
+
+// ch05-41. 係り受け解析結果の読み込み（文節・係り受け）
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Chunk {
+    pub morphs: Vec<Morph>,
+    // 係り先の文節番号。文末の文節は None
+    pub dst: Option<usize>,
+    // 係り元の文節番号
+    pub srcs: Vec<usize>,
+}
+
+impl Chunk {
+    // 句読点などの記号を除いた表層形
+    pub fn surface(&self) -> String {
+        self.morphs
+            .iter()
+            .filter(|morph| morph.pos != "記号")
+            .map(|morph| morph.surface.as_str())
+            .collect()
+    }
+
+    pub fn has_pos(&self, pos: &str) -> bool {
+        self.morphs.iter().any(|morph| morph.pos == pos)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sentence {
+    pub chunks: Vec<Chunk>,
+}
+
+impl Sentence {
+    pub fn morphs(&self) -> impl Iterator<Item = &Morph> {
+        self.chunks.iter().flat_map(|chunk| chunk.morphs.iter())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+}
+
+// 展開済みのファイルがあればそれを、無ければ zip の中の解析結果を読む。
+// zip に解析結果が無ければ組み込みの係り受け解析器の結果を使う。
+// 解析は時間がかかるので、結果は ai_lindera_parsed_path() に残して次から読む
+pub fn load_ai_sentences() -> Result<Vec<Sentence>, CabochaError> {
+    if Path::new(AI_PARSED_PATH).exists() {
+        return read_sentences(BufReader::new(File::open(AI_PARSED_PATH)?));
+    }
+    match read_sentences_from_zip(AI_ZIP_PATH, AI_PARSED_ENTRY) {
+        Err(CabochaError::Zip(ZipError::FileNotFound)) => {
+            let path = ai_lindera_parsed_path();
+            if is_fresh_cache(&path) {
+                return read_sentences(BufReader::new(File::open(&path)?));
+            }
+            parse_ai_text_to(&path)
+        }
+        result => result,
+    }
+}
+
+// ai.ja.txt を解析して CaboCha 形式で書き出す
+fn parse_ai_text() {
+    parse_ai_text_to(&ai_lindera_parsed_path()).expect("can't parse ai.ja.txt");
+}
+
+// 並行して読まれても書きかけの内容が見えないよう、一時ファイルに書いてから置き換える
+fn parse_ai_text_to(path: &str) -> Result<Vec<Sentence>, CabochaError> {
+    static WRITES: AtomicUsize = AtomicUsize::new(0);
+    let text = read_zip_entry(AI_ZIP_PATH, AI_TEXT_ENTRY)?;
+    let sentences = DependencyParser::default().parse_text(&text);
+    let temporary = format!(
+        "{}.{}.{}.tmp",
+        path,
+        process::id(),
+        WRITES.fetch_add(1, Ordering::SeqCst)
+    );
+    let mut out = BufWriter::new(File::create(&temporary)?);
+    for sentence in sentences.iter() {
+        write_sentence(&mut out, sentence)?;
+    }
+    out.flush()?;
+    drop(out);
+    fs::rename(&temporary, path)?;
+    return Ok(sentences);
+}
+
+pub fn parse_text_from<R: BufRead, W: Write>(
+    input: R,
+    mut out: W,
+    parser: &DependencyParser,
+) -> Result<(), CabochaError> {
+    let mut text = String::new();
+    for line in input.lines() {
+        text.push_str(&line?);
+        text.push('\n');
+    }
+    for sentence in parser.parse_text(&text) {
+        write_sentence(&mut out, &sentence)?;
+    }
+    return Ok(());
+}
+
+// ch05-40. 冒頭の説明文の形態素列
+fn print_first_morphs() {
+    let sentences = load_ai_sentences().expect("can't load parsed sentences");
+    write_first_morphs(
+        &sentences,
+        File::create("./data/chap05/morphs.txt").unwrap(),
+    );
+}
+
+pub fn print_first_morphs_from<R: BufRead, W: Write>(input: R, out: W) -> Result<(), CabochaError> {
+    write_first_morphs(&read_sentences(input)?, out);
+    return Ok(());
+}
+
+// 空でない最初の2文（見出しと説明文）の形態素を1行ずつ書き出す
+fn write_first_morphs<W: Write>(sentences: &[Sentence], mut out: W) {
+    sentences
+        .iter()
+        .filter(|sentence| !sentence.is_empty())
+        .take(2)
+        .flat_map(|sentence| sentence.morphs())
+        .for_each(|morph| {
+            writeln!(
+                out,
+                "{}\t{}\t{}\t{}",
+                morph.surface, morph.base, morph.pos, morph.pos1
+            )
+            .expect("Error during writeln");
+        });
+}
+
+// ch05-41. 冒頭の説明文の文節と係り先
+fn print_first_chunks() {
+    let sentences = load_ai_sentences().expect("can't load parsed sentences");
+    write_first_chunks(
+        &sentences,
+        File::create("./data/chap05/chunks.txt").unwrap(),
+    );
+}
+
+pub fn print_first_chunks_from<R: BufRead, W: Write>(input: R, out: W) -> Result<(), CabochaError> {
+    write_first_chunks(&read_sentences(input)?, out);
+    return Ok(());
+}
+
+fn write_first_chunks<W: Write>(sentences: &[Sentence], mut out: W) {
+    sentences
+        .iter()
+        .filter(|sentence| !sentence.is_empty())
+        .take(2)
+        .for_each(|sentence| {
+            for (i, chunk) in sentence.chunks.iter().enumerate() {
+                let dst = chunk.dst.map(|dst| dst as i64).unwrap_or(-1);
+                writeln!(out, "{}\t{}\t{}", i, chunk.surface(), dst).expect("Error during writeln");
+            }
+        });
+}
+
+// ch05-44. 係り受け木の可視化。冒頭の説明文を DOT と SVG で書き出す
+fn draw_dependency_tree() {
+    let sentences = load_ai_sentences().expect("can't load parsed sentences");
+    let sentence = sentences
+        .iter()
+        .filter(|sentence| !sentence.is_empty())
+        .nth(1)
+        .expect("no sentence to draw");
+    let tree = Tree::from_sentence(sentence);
+    write!(
+        File::create("./data/chap05/dependency_tree.dot").unwrap(),
+        "{}",
+        tree.to_dot()
+    )
+    .expect("Error during write");
+    write!(
+        File::create("./data/chap05/dependency_tree.svg").unwrap(),
+        "{}",
+        tree.to_svg()
+    )
+    .expect("Error during write");
+}
+
+// n 番目（0始まり）の文の係り受け木を SVG で書き出す
+pub fn draw_dependency_tree_from<R: BufRead, W: Write>(
+    input: R,
+    mut out: W,
+    n: usize,
+) -> Result<(), CabochaError> {
+    let sentences = read_sentences(input)?;
+    if let Some(sentence) = sentences.get(n) {
+        write!(out, "{}", Tree::from_sentence(sentence).to_svg())?;
+    }
+    return Ok(());
+}
+
+// ch03-23 のセクション構造を木として書き出す
+fn draw_section_tree() {
+    let articles = load_json("data/jawiki-country.json.gz", "イギリス");
+    let article = articles.get(0).expect("fail to load イギリス article");
+    let tree = Tree::from_sections(article.title(), &extract_sections(article));
+    write!(
+        File::create("./data/chap05/section_tree.dot").unwrap(),
+        "{}",
+        tree.to_dot()
+    )
+    .expect("Error during write");
+    write!(
+        File::create("./data/chap05/section_tree.svg").unwrap(),
+        "{}",
+        tree.to_svg()
+    )
+    .expect("Error during write");
+}
+
+// ch05-45. 動詞の格パターンの抽出
+fn print_case_patterns() {
+    let sentences = load_ai_sentences().expect("can't load parsed sentences");
+    write_case_frames(
+        &sentences,
+        File::create("./data/chap05/case_patterns.txt").unwrap(),
+        |sentence| CaseFrameExtractor::new().extract(sentence),
+        |frame| frame.case_pattern(),
+    );
+}
+
+pub fn print_case_patterns_from<R: BufRead, W: Write>(
+    input: R,
+    out: W,
+) -> Result<(), CabochaError> {
+    write_case_frames(
+        &read_sentences(input)?,
+        out,
+        |sentence| CaseFrameExtractor::new().extract(sentence),
+        |frame| frame.case_pattern(),
+    );
+    return Ok(());
+}
+
+// ch05-46. 動詞の格フレーム情報の抽出
+fn print_case_frames() {
+    let sentences = load_ai_sentences().expect("can't load parsed sentences");
+    write_case_frames(
+        &sentences,
+        File::create("./data/chap05/case_frames.txt").unwrap(),
+        |sentence| CaseFrameExtractor::new().extract(sentence),
+        |frame| frame.case_frame(),
+    );
+}
+
+pub fn print_case_frames_from<R: BufRead, W: Write>(input: R, out: W) -> Result<(), CabochaError> {
+    write_case_frames(
+        &read_sentences(input)?,
+        out,
+        |sentence| CaseFrameExtractor::new().extract(sentence),
+        |frame| frame.case_frame(),
+    );
+    return Ok(());
+}
+
+// ch05-47. 機能動詞構文のマイニング
+fn print_functional_verbs() {
+    let sentences = load_ai_sentences().expect("can't load parsed sentences");
+    write_case_frames(
+        &sentences,
+        File::create("./data/chap05/functional_verbs.txt").unwrap(),
+        |sentence| CaseFrameExtractor::new().extract_functional_verbs(sentence),
+        |frame| frame.case_frame(),
+    );
+}
+
+pub fn print_functional_verbs_from<R: BufRead, W: Write>(
+    input: R,
+    out: W,
+) -> Result<(), CabochaError> {
+    write_case_frames(
+        &read_sentences(input)?,
+        out,
+        |sentence| CaseFrameExtractor::new().extract_functional_verbs(sentence),
+        |frame| frame.case_frame(),
+    );
+    return Ok(());
+}
+
+fn write_case_frames<W, E, F>(sentences: &[Sentence], mut out: W, extract: E, format: F)
+where
+    W: Write,
+    E: Fn(&Sentence) -> Vec<CaseFrame>,
+    F: Fn(&CaseFrame) -> String,
+{
+    sentences
+        .iter()
+        .flat_map(|sentence| extract(sentence))
+        .for_each(|frame| writeln!(out, "{}", format(&frame)).expect("Error during writeln"));
+}
+
+// 格助詞に絞ったコーパス全体の格フレーム統計
+fn print_case_statistics() {
+    let sentences = load_ai_sentences().expect("can't load parsed sentences");
+    write_case_statistics(
+        &sentences,
+        File::create("./data/chap05/case_statistics.tsv").unwrap(),
+        &CaseFrameExtractor::case_particles_only(),
+        3,
+    )
+    .expect("Error during write");
+}
+
+pub fn case_statistics_from<R: BufRead, W: Write>(
+    input: R,
+    out: W,
+    extractor: &CaseFrameExtractor,
+    min_frequency: u64,
+) -> Result<(), CabochaError> {
+    write_case_statistics(&read_sentences(input)?, out, extractor, min_frequency)?;
+    return Ok(());
+}
+
+fn write_case_statistics<W: Write>(
+    sentences: &[Sentence],
+    mut out: W,
+    extractor: &CaseFrameExtractor,
+    min_frequency: u64,
+) -> std::io::Result<()> {
+    let mut stats = CaseFrameStatistics::new();
+    sentences
+        .iter()
+        .flat_map(|sentence| extractor.extract(sentence))
+        .for_each(|frame| stats.add(&frame));
+    return stats.write_tsv(&mut out, min_frequency);
+}
+
+// ch05-48. 名詞から根へのパスの抽出
+fn print_noun_paths() {
+    let sentences = load_ai_sentences().expect("can't load parsed sentences");
+    write_noun_paths(
+        &sentences,
+        File::create("./data/chap05/noun_paths.txt").unwrap(),
+    );
+}
+
+pub fn print_noun_paths_from<R: BufRead, W: Write>(input: R, out: W) -> Result<(), CabochaError> {
+    write_noun_paths(&read_sentences(input)?, out);
+    return Ok(());
+}
+
+fn write_noun_paths<W: Write>(sentences: &[Sentence], mut out: W) {
+    sentences
+        .iter()
+        .flat_map(|sentence| noun_paths_to_root(sentence))
+        .for_each(|path| writeln!(out, "{}", path).expect("Error during writeln"));
+}
+
+// ch05-49. 名詞間の係り受けパスの抽出
+fn print_noun_pair_paths() {
+    let sentences = load_ai_sentences().expect("can't load parsed sentences");
+    write_noun_pair_paths(
+        &sentences,
+        File::create("./data/chap05/noun_pair_paths.txt").unwrap(),
+    );
+}
+
+pub fn print_noun_pair_paths_from<R: BufRead, W: Write>(
+    input: R,
+    out: W,
+) -> Result<(), CabochaError> {
+    write_noun_pair_paths(&read_sentences(input)?, out);
+    return Ok(());
+}
+
+fn write_noun_pair_paths<W: Write>(sentences: &[Sentence], mut out: W) {
+    for sentence in sentences.iter() {
+        for (_, _, path) in noun_pair_paths(sentence) {
+            writeln!(out, "{}", path.format(sentence)).expect("Error during writeln");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter05::answer::{
+        ai_lindera_parsed_path, case_statistics_from, draw_dependency_tree,
+        draw_dependency_tree_from, draw_section_tree, is_fresh_cache, load_ai_sentences,
+        parse_ai_text, parse_ai_text_to, parse_text_from, print_case_frames,
+        print_case_frames_from, print_case_patterns, print_case_patterns_from,
+        print_case_statistics, print_first_chunks, print_first_chunks_from, print_first_morphs,
+        print_first_morphs_from, print_functional_verbs, print_functional_verbs_from,
+        print_noun_pair_paths, print_noun_pair_paths_from, print_noun_paths, print_noun_paths_from,
+    };
+    use crate::chapter05::cabocha::read_sentences;
+    use crate::chapter05::case_frame::CaseFrameExtractor;
+    use crate::chapter05::parser::DependencyParser;
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::path::Path;
+
+    const PARSED: &str = "* 0 -1D 0/1 0.000000\n\
+                          人工\t名詞,一般,*,*,*,*,人工,ジンコウ,ジンコー\n\
+                          知能\t名詞,一般,*,*,*,*,知能,チノウ,チノー\n\
+                          EOS\n\
+                          EOS\n\
+                          * 0 1D 0/1 1.0\n\
+                          人工\t名詞,一般,*,*,*,*,人工,ジンコウ,ジンコー\n\
+                          知能\t名詞,一般,*,*,*,*,知能,チノウ,チノー\n\
+                          は\t助詞,係助詞,*,*,*,*,は,ハ,ワ\n\
+                          * 1 -1D 0/1 0.000000\n\
+                          分野\t名詞,一般,*,*,*,*,分野,ブンヤ,ブンヤ\n\
+                          だ\t助動詞,*,*,*,特殊・ダ,基本形,だ,ダ,ダ\n\
+                          。\t記号,句点,*,*,*,*,。,。,。\n\
+                          EOS\n\
+                          * 0 -1D 0/0 0.000000\n\
+                          概要\t名詞,一般,*,*,*,*,概要,ガイヨウ,ガイヨー\n\
+                          EOS\n";
+
+    #[test]
+    pub fn success_print_first_morphs_from_reader() {
+        let mut out: Vec<u8> = vec![];
+        print_first_morphs_from(PARSED.as_bytes(), &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(8, lines.len());
+        assert_eq!("人工\t人工\t名詞\t一般", lines[0]);
+        assert_eq!("。\t。\t記号\t句点", lines[7]);
+    }
+
+    #[test]
+    pub fn success_print_first_chunks_from_reader() {
+        let mut out: Vec<u8> = vec![];
+        print_first_chunks_from(PARSED.as_bytes(), &mut out).unwrap();
+        assert_eq!(
+            "0\t人工知能\t-1\n0\t人工知能は\t1\n1\t分野だ\t-1\n",
+            String::from_utf8(out).unwrap()
+        );
+        assert!(print_first_chunks_from("* x\nEOS\n".as_bytes(), &mut vec![]).is_err());
+    }
+
+    #[test]
+    pub fn success_output_first_morphs_and_chunks() {
+        print_first_morphs();
+        print_first_chunks();
+        assert!(Path::new("./data/chap05/chunks.txt").exists());
+        assert!(load_ai_sentences().unwrap().len() > 100);
+    }
+
+    #[test]
+    pub fn success_parse_text_from_reader() {
+        let mut out: Vec<u8> = vec![];
+        parse_text_from(
+            "人工知能\n\n人工知能は分野だ。\n".as_bytes(),
+            &mut out,
+            &DependencyParser::default(),
+        )
+        .unwrap();
+        let sentences = read_sentences(out.as_slice()).unwrap();
+        assert_eq!(3, sentences.len());
+        assert!(sentences[1].is_empty());
+    }
+
+    #[test]
+    pub fn success_output_parsed_ai_text() {
+        parse_ai_text();
+        let path = ai_lindera_parsed_path();
+        assert!(is_fresh_cache(&path));
+        let sentences = parse_ai_text_to(&path).unwrap();
+        let parsed = read_sentences(BufReader::new(File::open(&path).unwrap()));
+        assert_eq!(sentences, parsed.unwrap());
+    }
+
+    const VERBS: &str = "* 0 2D 0/1 0.0\n\
+                         人工知能\t名詞,一般,*,*,*,*,人工知能\n\
+                         が\t助詞,格助詞,*,*,*,*,が\n\
+                         * 1 2D 0/1 0.0\n\
+                         研究\t名詞,サ変接続,*,*,*,*,研究\n\
+                         を\t助詞,格助詞,*,*,*,*,を\n\
+                         * 2 -1D 0/0 0.0\n\
+                         進める\t動詞,自立,*,*,*,基本形,進める\n\
+                         EOS\n";
+
+    #[test]
+    pub fn success_print_case_frames_from_reader() {
+        let run = |f: fn(&[u8], &mut Vec<u8>) -> Result<(), _>| {
+            let mut out: Vec<u8> = vec![];
+            f(VERBS.as_bytes(), &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(
+            "進める\tが を\n",
+            run(|i, o| print_case_patterns_from(i, o))
+        );
+        assert_eq!(
+            "進める\tが を\t人工知能が 研究を\n",
+            run(|i, o| print_case_frames_from(i, o))
+        );
+        assert_eq!(
+            "研究を進める\tが\t人工知能が\n",
+            run(|i, o| print_functional_verbs_from(i, o))
+        );
+
+        let mut out: Vec<u8> = vec![];
+        case_statistics_from(
+            VERBS.as_bytes(),
+            &mut out,
+            &CaseFrameExtractor::case_particles_only(),
+            1,
+        )
+        .unwrap();
+        assert_eq!(
+            "predicate\tcase\tfrequency\tratio\n進める\tが\t1\t1.0000\n進める\tを\t1\t1.0000\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn success_output_case_frames() {
+        print_case_patterns();
+        print_case_frames();
+        print_functional_verbs();
+        print_case_statistics();
+        assert!(Path::new("./data/chap05/case_statistics.tsv").exists());
+    }
+
+    #[test]
+    pub fn success_print_noun_paths_from_reader() {
+        let mut out: Vec<u8> = vec![];
+        print_noun_paths_from(PARSED.as_bytes(), &mut out).unwrap();
+        assert_eq!(
+            "人工知能\n人工知能は -> 分野だ\n分野だ\n概要\n",
+            String::from_utf8(out).unwrap()
+        );
+
+        let mut out: Vec<u8> = vec![];
+        print_noun_pair_paths_from(PARSED.as_bytes(), &mut out).unwrap();
+        assert_eq!("Xは -> Yだ\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    pub fn success_output_noun_paths() {
+        print_noun_paths();
+        print_noun_pair_paths();
+        assert!(Path::new("./data/chap05/noun_pair_paths.txt").exists());
+    }
+
+    #[test]
+    pub fn success_draw_dependency_tree_from_reader() {
+        let mut out: Vec<u8> = vec![];
+        draw_dependency_tree_from(PARSED.as_bytes(), &mut out, 2).unwrap();
+        let svg = String::from_utf8(out).unwrap();
+        assert!(svg.contains(">人工知能は</text>"));
+        assert_eq!(1, svg.matches("marker-end").count());
+
+        let mut out: Vec<u8> = vec![];
+        draw_dependency_tree_from(PARSED.as_bytes(), &mut out, 10).unwrap();
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    pub fn success_output_dependency_tree() {
+        draw_dependency_tree();
+        assert!(Path::new("./data/chap05/dependency_tree.svg").exists());
+    }
+
+    #[test]
+    pub fn success_output_section_tree() {
+        draw_section_tree();
+        assert!(Path::new("./data/chap05/section_tree.dot").exists());
+    }
+}
@@ -0,0 +1,317 @@
+use crate::chapter05::answer::{Chunk, Morph, Sentence};
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, Read, Write};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+// CaboCha の格子形式（-f1）
+//
+//   * 0 2D 0/1 -0.764522      文節番号, 係り先（-1D は係り先なし）, 主辞/機能語, スコア
+//   人工\t名詞,一般,*,*,*,*,人工,ジンコウ,ジンコー
+//   EOS
+//
+// 固有表現の列（-n1）が付いていても読み飛ばす
+
+#[derive(Debug)]
+pub enum CabochaError {
+    Io(io::Error),
+    Zip(ZipError),
+    Syntax { line: usize, message: String },
+}
+
+impl fmt::Display for CabochaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CabochaError::Io(error) => write!(f, "Error occurred... {}", error),
+            CabochaError::Zip(error) => write!(f, "Error occurred in zip... {}", error),
+            CabochaError::Syntax { line, message } => {
+                write!(f, "Invalid CaboCha output at line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CabochaError {}
+
+impl From<io::Error> for CabochaError {
+    fn from(error: io::Error) -> Self {
+        CabochaError::Io(error)
+    }
+}
+
+impl From<ZipError> for CabochaError {
+    fn from(error: ZipError) -> Self {
+        CabochaError::Zip(error)
+    }
+}
+
+fn syntax_error(line: usize, message: &str) -> CabochaError {
+    CabochaError::Syntax {
+        line,
+        message: message.to_string(),
+    }
+}
+
+// EOS ごとに1文を返す。空の文（EOS だけの行）もそのまま返す
+pub struct CabochaReader<R: BufRead> {
+    lines: io::Lines<R>,
+    line_number: usize,
+}
+
+impl<R: BufRead> CabochaReader<R> {
+    pub fn new(input: R) -> CabochaReader<R> {
+        CabochaReader {
+            lines: input.lines(),
+            line_number: 0,
+        }
+    }
+
+    fn read_sentence(&mut self) -> Option<Result<Sentence, CabochaError>> {
+        let mut chunks: Vec<Chunk> = vec![];
+        let mut dsts: Vec<(usize, Option<usize>)> = vec![];
+        let mut started = false;
+        while let Some(line) = self.lines.next() {
+            self.line_number += 1;
+            let line = match line {
+                Ok(line) => line,
+                Err(error) => return Some(Err(error.into())),
+            };
+            // 空行だけなら文は始まっていない（EOS の後の空行で missing EOS にしない）
+            if line.is_empty() {
+                continue;
+            }
+            started = true;
+            if line == "EOS" {
+                return Some(build_sentence(chunks, dsts));
+            }
+            if line.starts_with("* ") {
+                match parse_chunk_header(&line) {
+                    Some((id, dst)) if id == chunks.len() => {
+                        dsts.push((self.line_number, dst));
+                        chunks.push(Chunk::default());
+                    }
+                    Some(_) => {
+                        return Some(Err(syntax_error(
+                            self.line_number,
+                            "chunk ids must be sequential",
+                        )))
+                    }
+                    None => return Some(Err(syntax_error(self.line_number, "broken chunk line"))),
+                }
+                continue;
+            }
+            let morph = match parse_morph(&line) {
+                Some(morph) => morph,
+                None => return Some(Err(syntax_error(self.line_number, "broken morph line"))),
+            };
+            match chunks.last_mut() {
+                Some(chunk) => chunk.morphs.push(morph),
+                None => {
+                    return Some(Err(syntax_error(
+                        self.line_number,
+                        "morph appears before chunk line",
+                    )))
+                }
+            }
+        }
+        if started {
+            return Some(Err(syntax_error(self.line_number, "missing EOS")));
+        }
+        return None;
+    }
+}
+
+impl<R: BufRead> Iterator for CabochaReader<R> {
+    type Item = Result<Sentence, CabochaError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_sentence()
+    }
+}
+
+// "* 0 2D 0/1 -0.764522" -> (0, Some(2))
+fn parse_chunk_header(line: &str) -> Option<(usize, Option<usize>)> {
+    let mut fields = line.split(' ').skip(1);
+    let id = fields.next()?.parse::<usize>().ok()?;
+    let dst = fields.next()?.strip_suffix('D')?.parse::<i64>().ok()?;
+    let dst = if dst < 0 { None } else { Some(dst as usize) };
+    return Some((id, dst));
+}
+
+// "人工\t名詞,一般,*,*,*,*,人工,ジンコウ,ジンコー"
+pub fn parse_morph(line: &str) -> Option<Morph> {
+    let mut columns = line.split('\t');
+    let surface = columns.next()?;
+    let features: Vec<&str> = columns.next()?.split(',').collect();
+    if features.len() < 2 {
+        return None;
+    }
+    let base = match features.get(6) {
+        Some(base) if *base != "*" => base,
+        _ => surface,
+    };
+    return Some(Morph {
+        surface: surface.to_string(),
+        base: base.to_string(),
+        pos: features[0].to_string(),
+        pos1: features[1].to_string(),
+    });
+}
+
+// 係り先は必ず後ろの文節なので、dst > i でなければ壊れた入力とみなす。
+// これで係り受けをたどる処理は循環を気にしなくてよい
+fn build_sentence(
+    mut chunks: Vec<Chunk>,
+    dsts: Vec<(usize, Option<usize>)>,
+) -> Result<Sentence, CabochaError> {
+    for (i, (line, dst)) in dsts.into_iter().enumerate() {
+        if let Some(dst) = dst {
+            if dst >= chunks.len() {
+                return Err(syntax_error(line, "dst is out of range"));
+            }
+            if dst <= i {
+                return Err(syntax_error(line, "dst must point to a later chunk"));
+            }
+            chunks[dst].srcs.push(i);
+        }
+        chunks[i].dst = dst;
+    }
+    return Ok(Sentence { chunks });
+}
+
+// CaboCha の格子形式で書き出す。素性は品詞,品詞細分類1と原形だけ埋める
+pub fn write_sentence<W: Write>(out: &mut W, sentence: &Sentence) -> io::Result<()> {
+    for (i, chunk) in sentence.chunks.iter().enumerate() {
+        let dst = chunk.dst.map(|dst| dst as i64).unwrap_or(-1);
+        let is_function = |morph: &Morph| matches!(morph.pos.as_str(), "助詞" | "助動詞" | "記号");
+        let head = chunk
+            .morphs
+            .iter()
+            .rposition(|morph| !is_function(morph))
+            .unwrap_or(0);
+        let func = chunk
+            .morphs
+            .iter()
+            .rposition(|morph| morph.pos != "記号")
+            .unwrap_or(head);
+        writeln!(out, "* {} {}D {}/{} 0.000000", i, dst, head, func)?;
+        for morph in chunk.morphs.iter() {
+            writeln!(
+                out,
+                "{}\t{},{},*,*,*,*,{}",
+                morph.surface, morph.pos, morph.pos1, morph.base
+            )?;
+        }
+    }
+    writeln!(out, "EOS")?;
+    return Ok(());
+}
+
+pub fn read_sentences<R: BufRead>(input: R) -> Result<Vec<Sentence>, CabochaError> {
+    CabochaReader::new(input).collect()
+}
+
+// zip の中のファイルを読み出す
+pub fn read_zip_entry(zip_path: &str, entry: &str) -> Result<String, CabochaError> {
+    let mut archive = ZipArchive::new(File::open(zip_path)?)?;
+    let mut file = archive.by_name(entry)?;
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    return Ok(text);
+}
+
+pub fn read_sentences_from_zip(zip_path: &str, entry: &str) -> Result<Vec<Sentence>, CabochaError> {
+    let text = read_zip_entry(zip_path, entry)?;
+    return read_sentences(text.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter05::cabocha::{
+        parse_morph, read_sentences, read_sentences_from_zip, write_sentence, CabochaError,
+    };
+    use std::fs::File;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    const PARSED: &str = "* 0 -1D 0/0 0.000000\n\
+                              人工\t名詞,一般,*,*,*,*,人工,ジンコウ,ジンコー\n\
+                              知能\t名詞,一般,*,*,*,*,知能,チノウ,チノー\n\
+                              EOS\n\
+                              EOS\n\
+                              * 0 2D 0/1 -1.0\n\
+                              吾輩\t名詞,代名詞,一般,*,*,*,吾輩,ワガハイ,ワガハイ\n\
+                              は\t助詞,係助詞,*,*,*,*,は,ハ,ワ\n\
+                              * 1 2D 0/0 -1.0\n\
+                              ここ\t名詞,代名詞,一般,*,*,*,ここ,ココ,ココ\tO\n\
+                              で\t助詞,格助詞,一般,*,*,*,で,デ,デ\tO\n\
+                              * 2 -1D 0/1 0.000000\n\
+                              見\t動詞,自立,*,*,一段,連用形,見る,ミ,ミ\n\
+                              た\t助動詞,*,*,*,特殊・タ,基本形,た,タ,タ\n\
+                              。\t記号,句点,*,*,*,*,。,。,。\n\
+                              EOS\n";
+
+    #[test]
+    pub fn success_read_sentences() {
+        let sentences = read_sentences(PARSED.as_bytes()).unwrap();
+        assert_eq!(3, sentences.len());
+        assert_eq!(1, sentences[0].chunks.len());
+        assert!(sentences[1].chunks.is_empty());
+        let chunks = &sentences[2].chunks;
+        assert_eq!(vec![Some(2), Some(2), None], {
+            chunks.iter().map(|chunk| chunk.dst).collect::<Vec<_>>()
+        });
+        assert_eq!(vec![0, 1], chunks[2].srcs);
+        assert_eq!("見る", chunks[2].morphs[0].base);
+        assert_eq!("格助詞", chunks[1].morphs[1].pos1);
+
+        let mut out: Vec<u8> = vec![];
+        sentences
+            .iter()
+            .for_each(|sentence| write_sentence(&mut out, sentence).unwrap());
+        assert_eq!(sentences, read_sentences(out.as_slice()).unwrap());
+        let trailing = format!("{}\n\n", PARSED);
+        assert_eq!(sentences, read_sentences(trailing.as_bytes()).unwrap());
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("* 1 2D 0/1 0.000000\nここ\t名詞,代名詞,*,*,*,*,ここ\n"));
+
+        let morph = parse_morph("ＡＩ\t名詞,固有名詞,組織,*,*,*,*").unwrap();
+        assert_eq!("ＡＩ", morph.base);
+        assert!(parse_morph("EOS").is_none());
+    }
+
+    #[test]
+    pub fn success_read_broken_sentences() {
+        let error = |text: &str| match read_sentences(text.as_bytes()) {
+            Err(CabochaError::Syntax { line, .. }) => line,
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(1, error("人工\t名詞,一般\nEOS\n"));
+        assert_eq!(2, error("* 0 -1D\n* 2 -1D\nEOS\n"));
+        assert_eq!(1, error("* 0 3D 0/0 0.0\nEOS\n"));
+        assert_eq!(1, error("* 0 0D 0/0 0.0\nEOS\n"));
+        assert_eq!(2, error("* 0 1D 0/0 0.0\n* 1 0D 0/0 0.0\nEOS\n"));
+        assert_eq!(2, error("* 0 -1D 0/0 0.0\n人工\n"));
+        assert_eq!(2, error("* 0 -1D 0/0 0.0\n人工\t名詞,一般\n"));
+    }
+
+    #[test]
+    pub fn success_read_sentences_from_zip() {
+        let path = "./data/chap05/cabocha_test.zip";
+        let mut zip = ZipWriter::new(File::create(path).unwrap());
+        zip.start_file("ai.ja.txt.parsed", FileOptions::default())
+            .unwrap();
+        zip.write_all(PARSED.as_bytes()).unwrap();
+        zip.finish().unwrap();
+        let sentences = read_sentences_from_zip(path, "ai.ja.txt.parsed").unwrap();
+        assert_eq!(3, sentences.len());
+        match read_sentences_from_zip(path, "not_found.parsed") {
+            Err(CabochaError::Zip(_)) => {}
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
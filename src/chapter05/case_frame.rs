@@ -0,0 +1,361 @@
+use crate::chapter05::answer::{Chunk, Sentence};
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+
+// 述語に係る文節とその格（文節の最右の助詞）
+#[derive(Clone, Debug, PartialEq)]
+pub struct Argument {
+    pub case: String,
+    // 文中の文節番号
+    pub chunk: usize,
+    pub surface: String,
+}
+
+// ch05-45..47. 動詞の格フレーム
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaseFrame {
+    // 動詞の原形。機能動詞構文では「サ変名詞＋を＋動詞の原形」
+    pub predicate: String,
+    pub chunk: usize,
+    // 格の辞書順、同じ格なら文中の順
+    pub arguments: Vec<Argument>,
+}
+
+impl CaseFrame {
+    pub fn cases(&self) -> Vec<&str> {
+        self.arguments
+            .iter()
+            .map(|argument| argument.case.as_str())
+            .collect()
+    }
+
+    // ch05-45 の形式 "始める\tで は"
+    pub fn case_pattern(&self) -> String {
+        format!("{}\t{}", self.predicate, self.cases().join(" "))
+    }
+
+    // ch05-46, 47 の形式 "始める\tで は\t会議で ジョンマッカーシーは"
+    pub fn case_frame(&self) -> String {
+        let surfaces: Vec<&str> = self
+            .arguments
+            .iter()
+            .map(|argument| argument.surface.as_str())
+            .collect();
+        format!("{}\t{}", self.case_pattern(), surfaces.join(" "))
+    }
+}
+
+// 格とみなす助詞を品詞細分類1で絞る。指定しなければ全ての助詞（ch05-45 と同じ）
+#[derive(Clone, Debug, Default)]
+pub struct CaseFrameExtractor {
+    particle_pos1: Vec<String>,
+}
+
+impl CaseFrameExtractor {
+    pub fn new() -> CaseFrameExtractor {
+        CaseFrameExtractor::default()
+    }
+
+    // 格助詞（が・を・に・で…）だけを格とする
+    pub fn case_particles_only() -> CaseFrameExtractor {
+        CaseFrameExtractor::new().with_particle_pos1(&["格助詞"])
+    }
+
+    pub fn with_particle_pos1<S: AsRef<str>>(mut self, pos1: &[S]) -> CaseFrameExtractor {
+        self.particle_pos1 = pos1.iter().map(|p| p.as_ref().to_string()).collect();
+        return self;
+    }
+
+    // 文節の最右の助詞
+    fn case_of(&self, chunk: &Chunk) -> Option<String> {
+        let particle = chunk
+            .morphs
+            .iter()
+            .rev()
+            .find(|morph| morph.pos == "助詞")?;
+        if !self.particle_pos1.is_empty() && !self.particle_pos1.contains(&particle.pos1) {
+            return None;
+        }
+        return Some(particle.surface.clone());
+    }
+
+    // ch05-45, 46. 動詞を含む文節ごとに、最左の動詞を述語として格フレームを作る
+    pub fn extract(&self, sentence: &Sentence) -> Vec<CaseFrame> {
+        let mut frames = vec![];
+        for (i, chunk) in sentence.chunks.iter().enumerate() {
+            let verb = match chunk.morphs.iter().find(|morph| morph.pos == "動詞") {
+                Some(verb) => verb,
+                None => continue,
+            };
+            let arguments = self.arguments(sentence, &chunk.srcs);
+            if arguments.is_empty() {
+                continue;
+            }
+            frames.push(CaseFrame {
+                predicate: verb.base.clone(),
+                chunk: i,
+                arguments,
+            });
+        }
+        return frames;
+    }
+
+    // ch05-47. 「サ変接続名詞＋を」の文節が係る動詞だけを取り出し、その文節を述語に含める
+    pub fn extract_functional_verbs(&self, sentence: &Sentence) -> Vec<CaseFrame> {
+        let mut frames = vec![];
+        for (i, chunk) in sentence.chunks.iter().enumerate() {
+            let verb = match chunk.morphs.iter().find(|morph| morph.pos == "動詞") {
+                Some(verb) => verb,
+                None => continue,
+            };
+            let object = match chunk
+                .srcs
+                .iter()
+                .find(|src| is_sahen_wo(&sentence.chunks[**src]))
+            {
+                Some(object) => *object,
+                None => continue,
+            };
+            let srcs: Vec<usize> = chunk
+                .srcs
+                .iter()
+                .filter(|src| **src != object)
+                .cloned()
+                .collect();
+            let arguments = self.arguments(sentence, &srcs);
+            if arguments.is_empty() {
+                continue;
+            }
+            frames.push(CaseFrame {
+                predicate: format!("{}{}", sentence.chunks[object].surface(), verb.base),
+                chunk: i,
+                arguments,
+            });
+        }
+        return frames;
+    }
+
+    fn arguments(&self, sentence: &Sentence, srcs: &[usize]) -> Vec<Argument> {
+        let mut arguments: Vec<Argument> = vec![];
+        for src in srcs.iter() {
+            let chunk = &sentence.chunks[*src];
+            if let Some(case) = self.case_of(chunk) {
+                arguments.push(Argument {
+                    case,
+                    chunk: *src,
+                    surface: chunk.surface(),
+                });
+            }
+        }
+        arguments.sort_by(|x, y| x.case.cmp(&y.case).then(x.chunk.cmp(&y.chunk)));
+        return arguments;
+    }
+}
+
+// 記号を除くと「サ変接続名詞＋を」だけの文節
+fn is_sahen_wo(chunk: &Chunk) -> bool {
+    let morphs: Vec<_> = chunk
+        .morphs
+        .iter()
+        .filter(|morph| morph.pos != "記号")
+        .collect();
+    match morphs.as_slice() {
+        [noun, wo] => {
+            noun.pos == "名詞" && noun.pos1 == "サ変接続" && wo.surface == "を" && wo.pos == "助詞"
+        }
+        _ => false,
+    }
+}
+
+// コーパス全体での述語と格の共起頻度
+#[derive(Debug, Default)]
+pub struct CaseFrameStatistics {
+    predicates: BTreeMap<String, u64>,
+    cases: BTreeMap<(String, String), u64>,
+    patterns: BTreeMap<String, u64>,
+}
+
+impl CaseFrameStatistics {
+    pub fn new() -> CaseFrameStatistics {
+        CaseFrameStatistics::default()
+    }
+
+    // 同じ格が2つあっても格ごとの頻度には1回と数える
+    pub fn add(&mut self, frame: &CaseFrame) {
+        *self.predicates.entry(frame.predicate.clone()).or_insert(0) += 1;
+        *self.patterns.entry(frame.case_pattern()).or_insert(0) += 1;
+        let mut cases = frame.cases();
+        cases.dedup();
+        for case in cases {
+            *self
+                .cases
+                .entry((frame.predicate.clone(), case.to_string()))
+                .or_insert(0) += 1;
+        }
+    }
+
+    pub fn predicate_count(&self, predicate: &str) -> u64 {
+        *self.predicates.get(predicate).unwrap_or(&0)
+    }
+
+    pub fn case_count(&self, predicate: &str, case: &str) -> u64 {
+        *self
+            .cases
+            .get(&(predicate.to_string(), case.to_string()))
+            .unwrap_or(&0)
+    }
+
+    // 頻度の降順、同じ頻度なら辞書順
+    pub fn top_predicates(&self, n: usize) -> Vec<(&str, u64)> {
+        top(&self.predicates, n)
+    }
+
+    // ch05-45 の形式の行ごとの頻度（`sort | uniq -c | sort -nr` 相当）
+    pub fn top_patterns(&self, n: usize) -> Vec<(&str, u64)> {
+        top(&self.patterns, n)
+    }
+
+    // 頻度が min_frequency 以上の述語について、格ごとの頻度と述語中の割合を書き出す
+    pub fn write_tsv<W: Write>(&self, out: &mut W, min_frequency: u64) -> io::Result<()> {
+        writeln!(out, "predicate\tcase\tfrequency\tratio")?;
+        for (predicate, count) in top(&self.predicates, self.predicates.len()) {
+            if count < min_frequency {
+                break;
+            }
+            let mut cases: Vec<(&str, u64)> = self
+                .cases
+                .range((predicate.to_string(), String::new())..)
+                .take_while(|((p, _), _)| p == predicate)
+                .map(|((_, case), count)| (case.as_str(), *count))
+                .collect();
+            cases.sort_by(|x, y| y.1.cmp(&x.1).then(x.0.cmp(y.0)));
+            for (case, frequency) in cases {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{:.4}",
+                    predicate,
+                    case,
+                    frequency,
+                    frequency as f64 / count as f64
+                )?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+fn top(counts: &BTreeMap<String, u64>, n: usize) -> Vec<(&str, u64)> {
+    let mut items: Vec<(&str, u64)> = counts
+        .iter()
+        .map(|(key, count)| (key.as_str(), *count))
+        .collect();
+    items.sort_by(|x, y| y.1.cmp(&x.1).then(x.0.cmp(y.0)));
+    items.truncate(n);
+    return items;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter05::cabocha::read_sentences;
+    use crate::chapter05::case_frame::{CaseFrameExtractor, CaseFrameStatistics};
+
+    // 「ジョン・マッカーシーは会議で用語を始めた。」「彼は研究を行い、論文で議論を始めた。」
+    const PARSED: &str = "* 0 3D 0/1 0.0\n\
+                          ジョン\t名詞,固有名詞,*,*,*,*,*\n\
+                          ・\t記号,一般,*,*,*,*,・\n\
+                          マッカーシー\t名詞,固有名詞,*,*,*,*,*\n\
+                          は\t助詞,係助詞,*,*,*,*,は\n\
+                          * 1 3D 0/1 0.0\n\
+                          会議\t名詞,サ変接続,*,*,*,*,会議\n\
+                          で\t助詞,格助詞,*,*,*,*,で\n\
+                          * 2 3D 0/1 0.0\n\
+                          用語\t名詞,一般,*,*,*,*,用語\n\
+                          を\t助詞,格助詞,*,*,*,*,を\n\
+                          * 3 -1D 0/1 0.0\n\
+                          始め\t動詞,自立,*,*,*,*,始める\n\
+                          た\t助動詞,*,*,*,*,*,た\n\
+                          。\t記号,句点,*,*,*,*,。\n\
+                          EOS\n\
+                          * 0 5D 0/1 0.0\n\
+                          彼\t名詞,代名詞,*,*,*,*,彼\n\
+                          は\t助詞,係助詞,*,*,*,*,は\n\
+                          * 1 2D 0/1 0.0\n\
+                          研究\t名詞,サ変接続,*,*,*,*,研究\n\
+                          を\t助詞,格助詞,*,*,*,*,を\n\
+                          * 2 5D 0/1 0.0\n\
+                          行い\t動詞,自立,*,*,*,*,行う\n\
+                          、\t記号,読点,*,*,*,*,、\n\
+                          * 3 5D 0/1 0.0\n\
+                          論文\t名詞,一般,*,*,*,*,論文\n\
+                          で\t助詞,格助詞,*,*,*,*,で\n\
+                          * 4 5D 0/1 0.0\n\
+                          議論\t名詞,サ変接続,*,*,*,*,議論\n\
+                          を\t助詞,格助詞,*,*,*,*,を\n\
+                          * 5 -1D 0/1 0.0\n\
+                          始め\t動詞,自立,*,*,*,*,始める\n\
+                          た\t助動詞,*,*,*,*,*,た\n\
+                          。\t記号,句点,*,*,*,*,。\n\
+                          EOS\n";
+
+    #[test]
+    pub fn success_extract_case_frames() {
+        let sentences = read_sentences(PARSED.as_bytes()).unwrap();
+        let extractor = CaseFrameExtractor::new();
+        let frames = extractor.extract(&sentences[0]);
+        assert_eq!(1, frames.len());
+        assert_eq!("始める\tで は を", frames[0].case_pattern());
+        assert_eq!(
+            "始める\tで は を\t会議で ジョンマッカーシーは 用語を",
+            frames[0].case_frame()
+        );
+
+        // 「行い」には「研究を」だけが係る。「始め」の格は辞書順に並べる
+        let patterns: Vec<String> = extractor
+            .extract(&sentences[1])
+            .iter()
+            .map(|frame| frame.case_pattern())
+            .collect();
+        assert_eq!(vec!["行う\tを", "始める\tで は を"], patterns);
+
+        let frames = CaseFrameExtractor::case_particles_only().extract(&sentences[0]);
+        assert_eq!(vec!["で", "を"], frames[0].cases());
+    }
+
+    #[test]
+    pub fn success_extract_functional_verbs() {
+        let sentences = read_sentences(PARSED.as_bytes()).unwrap();
+        let extractor = CaseFrameExtractor::new();
+        assert!(extractor.extract_functional_verbs(&sentences[0]).is_empty());
+        let frames = extractor.extract_functional_verbs(&sentences[1]);
+        // 「研究を行い」には他に係る文節がないので出力しない
+        assert_eq!(1, frames.len());
+        assert_eq!("議論を始める\tで は\t論文で 彼は", frames[0].case_frame());
+    }
+
+    #[test]
+    pub fn success_case_frame_statistics() {
+        let sentences = read_sentences(PARSED.as_bytes()).unwrap();
+        let extractor = CaseFrameExtractor::new();
+        let mut stats = CaseFrameStatistics::new();
+        sentences
+            .iter()
+            .flat_map(|sentence| extractor.extract(sentence))
+            .for_each(|frame| stats.add(&frame));
+        assert_eq!(2, stats.predicate_count("始める"));
+        assert_eq!(2, stats.case_count("始める", "を"));
+        assert_eq!(0, stats.case_count("行う", "で"));
+        assert_eq!(vec![("始める", 2), ("行う", 1)], stats.top_predicates(5));
+        assert_eq!(vec![("始める\tで は を", 2)], stats.top_patterns(1));
+
+        let mut out: Vec<u8> = vec![];
+        stats.write_tsv(&mut out, 2).unwrap();
+        assert_eq!(
+            "predicate\tcase\tfrequency\tratio\n\
+             始める\tで\t2\t1.0000\n\
+             始める\tは\t2\t1.0000\n\
+             始める\tを\t2\t1.0000\n",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
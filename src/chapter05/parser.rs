@@ -0,0 +1,321 @@
+use crate::chapter04::answer::{NekoParser, Token};
+use crate::chapter05::answer::{Chunk, Morph, Sentence};
+use lindera::tokenizer::Tokenizer;
+
+// 解析結果が変わる修正をしたら上げる。解析結果のキャッシュはこの版ごとに分けて置く
+pub const PARSER_VERSION: u32 = 1;
+
+// lindera の形態素解析結果から文節を作り、規則で係り受けを決める
+//
+// 文節: 自立語の前で区切る。助詞・助動詞・記号・接尾辞・非自立語は直前の文節に付け、
+//       名詞の連続（複合名詞）とサ変名詞＋する、接頭詞と開き括弧の直後は区切らない
+// 係り受け: 右の文節から順に係り先を決める。交差しないように、候補は直後の文節から
+//          係り先をたどった文節だけに絞り、文節の種類ごとに最初に条件を満たすものを選ぶ
+//          （主題の「は」「も」は文末の文節に係る）
+#[derive(Debug, Default)]
+pub struct DependencyParser {
+    tokenizer: NekoParser,
+}
+
+impl DependencyParser {
+    pub fn new(tokenizer: NekoParser) -> DependencyParser {
+        DependencyParser { tokenizer }
+    }
+
+    // 1行ずつ文に分けて解析する。空行は CaboCha と同じく空の文にする
+    pub fn parse_text(&self, text: &str) -> Vec<Sentence> {
+        let mut tokenizer = self.tokenizer.new_tokenizer();
+        return text
+            .lines()
+            .flat_map(|line| self.parse_line(&mut tokenizer, line))
+            .collect();
+    }
+
+    pub fn parse_line(&self, tokenizer: &mut Tokenizer, line: &str) -> Vec<Sentence> {
+        let sentences: Vec<Sentence> = self
+            .tokenizer
+            .tokenize_sentences(tokenizer, line, 0)
+            .iter()
+            .map(|tokens| parse_tokens(tokens))
+            .collect();
+        if sentences.is_empty() {
+            return vec![Sentence::default()];
+        }
+        return sentences;
+    }
+}
+
+// 1文分のトークン列を文節に分けて係り受けを付ける
+pub fn parse_tokens(tokens: &[Token]) -> Sentence {
+    let bunsetsu = chunk_tokens(tokens);
+    let dsts = attach(&bunsetsu);
+    let mut chunks: Vec<Chunk> = bunsetsu
+        .iter()
+        .zip(dsts.iter())
+        .map(|(tokens, dst)| Chunk {
+            morphs: tokens.iter().map(|token| to_morph(token)).collect(),
+            dst: *dst,
+            srcs: vec![],
+        })
+        .collect();
+    for (i, dst) in dsts.iter().enumerate() {
+        if let Some(dst) = dst {
+            chunks[*dst].srcs.push(i);
+        }
+    }
+    return Sentence { chunks };
+}
+
+fn to_morph(token: &Token) -> Morph {
+    let base = match token.base() {
+        "" | "*" => token.surface(),
+        base => base,
+    };
+    Morph {
+        surface: token.surface().to_string(),
+        base: base.to_string(),
+        pos: token.pos().to_string(),
+        pos1: token.pos1().to_string(),
+    }
+}
+
+// 未知語は名詞として扱う
+fn is_noun(token: &Token) -> bool {
+    token.pos() == "名詞" || token.is_unknown() || token.pos().is_empty()
+}
+
+fn is_function_word(token: &Token) -> bool {
+    matches!(token.pos(), "助詞" | "助動詞")
+}
+
+fn is_symbol(token: &Token) -> bool {
+    token.pos() == "記号"
+}
+
+// 直前の文節に付けるトークンか
+fn attaches_to_previous(previous: &Token, token: &Token) -> bool {
+    if previous.pos() == "接頭詞" || previous.pos1() == "括弧開" {
+        return true;
+    }
+    if token.pos1() == "括弧開" {
+        return false;
+    }
+    if is_function_word(token) || is_symbol(token) {
+        return true;
+    }
+    if matches!(token.pos1(), "接尾" | "非自立") {
+        return true;
+    }
+    if is_function_word(previous) || is_symbol(previous) {
+        return false;
+    }
+    if is_noun(token) && is_noun(previous) {
+        return true;
+    }
+    // サ変名詞＋する（研究する、利用できる）
+    return token.pos() == "動詞"
+        && matches!(token.base(), "する" | "できる")
+        && previous.pos1() == "サ変接続";
+}
+
+pub fn chunk_tokens(tokens: &[Token]) -> Vec<Vec<Token>> {
+    let mut chunks: Vec<Vec<Token>> = vec![];
+    for token in tokens {
+        match chunks.last_mut() {
+            Some(chunk) if attaches_to_previous(chunk.last().unwrap(), token) => {
+                chunk.push(token.clone())
+            }
+            _ => chunks.push(vec![token.clone()]),
+        }
+    }
+    return chunks;
+}
+
+// 文節が何に係るか
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Modifier {
+    // 体言に係る（の、連体詞、連体形の用言、並立助詞）
+    Noun,
+    // 用言に係る（格助詞、連用形、副詞など）
+    Predicate,
+    // 主題（は、も）。文末の文節に係る
+    Topic,
+    // 読点付きの連用修飾。読点の付いた用言か文末の文節に係る
+    Comma,
+    // 直後の文節に係る
+    Next,
+}
+
+fn classify(chunk: &[Token]) -> Modifier {
+    let comma = has_comma(chunk);
+    let last = match chunk.iter().rev().find(|token| !is_symbol(token)) {
+        Some(last) => last,
+        None => return Modifier::Next,
+    };
+    let modifier = match (last.pos(), last.pos1()) {
+        ("助詞", "連体化") | ("助詞", "並立助詞") => Modifier::Noun,
+        ("助詞", "係助詞") => Modifier::Topic,
+        ("助詞", _) => Modifier::Predicate,
+        ("連体詞", _) => Modifier::Noun,
+        ("副詞", _) | ("接続詞", _) => Modifier::Predicate,
+        ("名詞", "副詞可能") => Modifier::Predicate,
+        ("動詞", _) | ("形容詞", _) | ("助動詞", _) => match last.conjugation_form() {
+            "基本形" | "連体形" | "体言接続" if !comma => Modifier::Noun,
+            _ => Modifier::Predicate,
+        },
+        _ => Modifier::Next,
+    };
+    if comma && modifier == Modifier::Predicate {
+        return Modifier::Comma;
+    }
+    return modifier;
+}
+
+fn is_predicate(chunk: &[Token]) -> bool {
+    chunk
+        .iter()
+        .any(|token| matches!(token.pos(), "動詞" | "形容詞") && token.pos1() != "非自立")
+        || chunk
+            .iter()
+            .rev()
+            .find(|token| !is_symbol(token))
+            .map(|token| token.pos() == "助動詞")
+            .unwrap_or(false)
+}
+
+fn is_nominal(chunk: &[Token]) -> bool {
+    chunk
+        .iter()
+        .rev()
+        .find(|token| !is_function_word(token) && !is_symbol(token))
+        .map(|token| is_noun(token))
+        .unwrap_or(false)
+}
+
+fn has_comma(chunk: &[Token]) -> bool {
+    chunk.iter().any(|token| token.pos1() == "読点")
+}
+
+// 各文節の係り先。最後の文節は None
+fn attach(chunks: &[Vec<Token>]) -> Vec<Option<usize>> {
+    let mut dsts: Vec<Option<usize>> = vec![None; chunks.len()];
+    for i in (0..chunks.len().saturating_sub(1)).rev() {
+        // 直後の文節から係り先をたどった列が、交差しない係り先の候補
+        let mut candidates = vec![i + 1];
+        while let Some(dst) = dsts[*candidates.last().unwrap()] {
+            candidates.push(dst);
+        }
+        let root = *candidates.last().unwrap();
+        let dst = match classify(&chunks[i]) {
+            Modifier::Noun => candidates
+                .iter()
+                .find(|j| is_nominal(&chunks[**j]))
+                .cloned()
+                .unwrap_or(i + 1),
+            Modifier::Predicate => candidates
+                .iter()
+                .find(|j| is_predicate(&chunks[**j]))
+                .cloned()
+                .unwrap_or(root),
+            Modifier::Topic => root,
+            Modifier::Comma => candidates
+                .iter()
+                .find(|j| is_predicate(&chunks[**j]) && (has_comma(&chunks[**j]) || **j == root))
+                .cloned()
+                .unwrap_or(root),
+            Modifier::Next => i + 1,
+        };
+        dsts[i] = Some(dst);
+    }
+    return dsts;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter04::answer::Token;
+    use crate::chapter05::parser::{chunk_tokens, parse_tokens, DependencyParser};
+    use crate::test_support::tokens;
+
+    fn surfaces(chunks: &[Vec<Token>]) -> Vec<String> {
+        chunks
+            .iter()
+            .map(|chunk| chunk.iter().map(|token| token.surface()).collect())
+            .collect()
+    }
+
+    // 吾輩はここで始めて人間というものを見た。
+    fn neko() -> Vec<Token> {
+        tokens(
+            "吾輩/吾輩/名詞/代名詞 は/は/助詞/係助詞 ここ/ここ/名詞/代名詞 で/で/助詞/格助詞 \
+             始め/始める/動詞/自立/連用形 て/て/助詞/接続助詞 人間/人間/名詞/一般 \
+             という/という/助詞/格助詞 もの/もの/名詞/非自立 を/を/助詞/格助詞 \
+             見/見る/動詞/自立/連用形 た/た/助動詞//基本形 。/。/記号/句点",
+        )
+    }
+
+    #[test]
+    pub fn success_chunk_tokens() {
+        assert_eq!(
+            vec!["吾輩は", "ここで", "始めて", "人間というものを", "見た。"],
+            surfaces(&chunk_tokens(&neko()))
+        );
+        // 複合名詞、サ変名詞＋する、接頭詞、括弧
+        let chunks = chunk_tokens(&tokens(
+            "人工/人工/名詞/一般 知能/知能/名詞/一般 を/を/助詞/格助詞 \
+             研究/研究/名詞/サ変接続 する/する/動詞/自立/基本形 \
+             「/「/記号/括弧開 お/お/接頭詞/名詞接続 茶/茶/名詞/一般 」/」/記号/括弧閉",
+        ));
+        assert_eq!(
+            vec!["人工知能を", "研究する", "「お茶」"],
+            surfaces(&chunks)
+        );
+    }
+
+    #[test]
+    pub fn success_parse_tokens() {
+        let sentence = parse_tokens(&neko());
+        let dsts: Vec<Option<usize>> = sentence.chunks.iter().map(|chunk| chunk.dst).collect();
+        assert_eq!(vec![Some(4), Some(2), Some(4), Some(4), None], dsts);
+        assert_eq!(vec![0, 2, 3], sentence.chunks[4].srcs);
+        assert_eq!("見る", sentence.chunks[4].morphs[0].base);
+
+        // 連体修飾は直後の名詞に係る: 計算機科学の一分野を指す語
+        let sentence = parse_tokens(&tokens(
+            "計算/計算/名詞/サ変接続 機/機/名詞/接尾 科学/科学/名詞/一般 の/の/助詞/連体化 \
+             一/一/名詞/数 分野/分野/名詞/一般 を/を/助詞/格助詞 \
+             指す/指す/動詞/自立/基本形 語/語/名詞/一般",
+        ));
+        let dsts: Vec<Option<usize>> = sentence.chunks.iter().map(|chunk| chunk.dst).collect();
+        assert_eq!(vec![Some(1), Some(2), Some(3), None], dsts);
+    }
+
+    #[test]
+    pub fn success_dependencies_do_not_cross() {
+        let sentence = parse_tokens(&tokens(
+            "彼/彼/名詞/代名詞 は/は/助詞/係助詞 、/、/記号/読点 赤い/赤い/形容詞/自立/基本形 \
+             本/本/名詞/一般 を/を/助詞/格助詞 読ん/読む/動詞/自立/連用タ接続 で/で/助詞/接続助詞 \
+             、/、/記号/読点 寝/寝る/動詞/自立/連用形 た/た/助動詞//基本形",
+        ));
+        let dsts: Vec<Option<usize>> = sentence.chunks.iter().map(|chunk| chunk.dst).collect();
+        assert_eq!(vec![Some(4), Some(2), Some(3), Some(4), None], dsts);
+        for (i, chunk) in sentence.chunks.iter().enumerate() {
+            if let Some(dst) = chunk.dst {
+                for j in (i + 1)..dst {
+                    assert!(sentence.chunks[j].dst.unwrap() <= dst);
+                }
+            }
+        }
+    }
+
+    #[test]
+    pub fn success_parse_text() {
+        let parser = DependencyParser::default();
+        let sentences = parser.parse_text("人工知能\n\n人工知能は分野だ。概要を見る。\n");
+        assert_eq!(4, sentences.len());
+        assert!(sentences[1].is_empty());
+        assert!(sentences
+            .iter()
+            .filter(|sentence| !sentence.is_empty())
+            .all(|sentence| sentence.chunks.last().unwrap().dst.is_none()));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::chapter05::answer::{Chunk, Sentence};
+
+// 2つの名詞句を結ぶ係り受けのパス（文節番号の列）
+#[derive(Clone, Debug, PartialEq)]
+pub enum NounPath {
+    // 文節 i から根に向かうパスの途中に文節 j がある。i..=j
+    Direct(Vec<usize>),
+    // 文節 i と j から根に向かうパスが文節 common で初めて交わる。
+    // left, right は common を含まない
+    Branched {
+        left: Vec<usize>,
+        right: Vec<usize>,
+        common: usize,
+    },
+}
+
+impl NounPath {
+    // ch05-49 の形式。始点の名詞句を X、終点の名詞句を Y に置き換える
+    //   "Xに関する -> 最初の -> Yで"
+    //   "Xは | Yに関する -> 最初の -> 会議で | 作り出した"
+    pub fn format(&self, sentence: &Sentence) -> String {
+        match self {
+            NounPath::Direct(path) => {
+                let last = path.len() - 1;
+                let surfaces: Vec<String> = path
+                    .iter()
+                    .enumerate()
+                    .map(|(n, i)| match n {
+                        0 => replace_noun(&sentence.chunks[*i], "X"),
+                        n if n == last => replace_noun(&sentence.chunks[*i], "Y"),
+                        _ => sentence.chunks[*i].surface(),
+                    })
+                    .collect();
+                surfaces.join(" -> ")
+            }
+            NounPath::Branched {
+                left,
+                right,
+                common,
+            } => format!(
+                "{} | {} | {}",
+                format_from(sentence, left, "X"),
+                format_from(sentence, right, "Y"),
+                sentence.chunks[*common].surface()
+            ),
+        }
+    }
+}
+
+// 先頭の文節だけ名詞句を symbol に置き換えて " -> " でつなぐ
+fn format_from(sentence: &Sentence, path: &[usize], symbol: &str) -> String {
+    let surfaces: Vec<String> = path
+        .iter()
+        .enumerate()
+        .map(|(n, i)| match n {
+            0 => replace_noun(&sentence.chunks[*i], symbol),
+            _ => sentence.chunks[*i].surface(),
+        })
+        .collect();
+    surfaces.join(" -> ")
+}
+
+// 記号を除いた表層形のうち、最初の名詞の連続を symbol に置き換える
+pub fn replace_noun(chunk: &Chunk, symbol: &str) -> String {
+    let mut text = String::new();
+    let mut replaced = false;
+    let mut in_noun = false;
+    for morph in chunk.morphs.iter().filter(|morph| morph.pos != "記号") {
+        if morph.pos == "名詞" && !replaced {
+            if !in_noun {
+                text.push_str(symbol);
+                in_noun = true;
+            }
+            continue;
+        }
+        if in_noun {
+            replaced = true;
+            in_noun = false;
+        }
+        text.push_str(&morph.surface);
+    }
+    return text;
+}
+
+// 名詞を含む文節の番号
+pub fn noun_chunks(sentence: &Sentence) -> Vec<usize> {
+    sentence
+        .chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| chunk.has_pos("名詞"))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// 文節 from から根までの文節番号（from と根を含む）
+pub fn path_to_root(sentence: &Sentence, from: usize) -> Vec<usize> {
+    let mut path = vec![from];
+    let mut current = from;
+    // 係り先は常に後ろの文節（cabocha::read_sentences で確認済み）なので必ず根に着く
+    while let Some(dst) = sentence.chunks[current].dst {
+        path.push(dst);
+        current = dst;
+    }
+    return path;
+}
+
+// 文節 i から j への最短パス。根が異なり交わらなければ None
+pub fn shortest_path(sentence: &Sentence, i: usize, j: usize) -> Option<NounPath> {
+    let from_i = path_to_root(sentence, i);
+    if let Some(position) = from_i.iter().position(|k| *k == j) {
+        return Some(NounPath::Direct(from_i[..=position].to_vec()));
+    }
+    let from_j = path_to_root(sentence, j);
+    let (left, common) = from_i
+        .iter()
+        .enumerate()
+        .find(|(_, k)| from_j.contains(k))
+        .map(|(n, k)| (from_i[..n].to_vec(), *k))?;
+    let right = from_j
+        .iter()
+        .take_while(|k| **k != common)
+        .cloned()
+        .collect();
+    return Some(NounPath::Branched {
+        left,
+        right,
+        common,
+    });
+}
+
+// ch05-48. 名詞を含む各文節から根へのパス "人工知能は -> 分野だ"
+pub fn noun_paths_to_root(sentence: &Sentence) -> Vec<String> {
+    noun_chunks(sentence)
+        .into_iter()
+        .map(|i| {
+            let surfaces: Vec<String> = path_to_root(sentence, i)
+                .iter()
+                .map(|k| sentence.chunks[*k].surface())
+                .collect();
+            surfaces.join(" -> ")
+        })
+        .collect()
+}
+
+// ch05-49. 名詞を含む文節の全ての組 (i < j) の最短パス
+pub fn noun_pair_paths(sentence: &Sentence) -> Vec<(usize, usize, NounPath)> {
+    let nouns = noun_chunks(sentence);
+    let mut paths = vec![];
+    for (n, i) in nouns.iter().enumerate() {
+        for j in nouns[n + 1..].iter() {
+            if let Some(path) = shortest_path(sentence, *i, *j) {
+                paths.push((*i, *j, path));
+            }
+        }
+    }
+    return paths;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter05::cabocha::read_sentences;
+    use crate::chapter05::path::{
+        noun_pair_paths, noun_paths_to_root, path_to_root, replace_noun, shortest_path, NounPath,
+    };
+
+    // ジョン・マッカーシーは AIに関する 最初の 会議で 人工知能という 用語を 作り出した。
+    const PARSED: &str = "* 0 6D 0/1 0.0\n\
+                          ジョン\t名詞,固有名詞,*,*,*,*,*\n\
+                          ・\t記号,一般,*,*,*,*,・\n\
+                          マッカーシー\t名詞,固有名詞,*,*,*,*,*\n\
+                          は\t助詞,係助詞,*,*,*,*,は\n\
+                          * 1 2D 0/1 0.0\n\
+                          AI\t名詞,一般,*,*,*,*,*\n\
+                          に関する\t助詞,格助詞,連語,*,*,*,に関する\n\
+                          * 2 3D 0/1 0.0\n\
+                          最初\t名詞,副詞可能,*,*,*,*,最初\n\
+                          の\t助詞,連体化,*,*,*,*,の\n\
+                          * 3 6D 0/1 0.0\n\
+                          会議\t名詞,サ変接続,*,*,*,*,会議\n\
+                          で\t助詞,格助詞,一般,*,*,*,で\n\
+                          * 4 5D 0/1 0.0\n\
+                          人工\t名詞,一般,*,*,*,*,人工\n\
+                          知能\t名詞,一般,*,*,*,*,知能\n\
+                          という\t助詞,格助詞,連語,*,*,*,という\n\
+                          * 5 6D 0/1 0.0\n\
+                          用語\t名詞,一般,*,*,*,*,用語\n\
+                          を\t助詞,格助詞,一般,*,*,*,を\n\
+                          * 6 -1D 0/1 0.0\n\
+                          作り出し\t動詞,自立,*,*,*,*,作り出す\n\
+                          た\t助動詞,*,*,*,*,*,た\n\
+                          。\t記号,句点,*,*,*,*,。\n\
+                          EOS\n";
+
+    #[test]
+    pub fn success_noun_paths_to_root() {
+        let sentence = &read_sentences(PARSED.as_bytes()).unwrap()[0];
+        assert_eq!(vec![1, 2, 3, 6], path_to_root(sentence, 1));
+        assert_eq!(vec![6], path_to_root(sentence, 6));
+        let paths = noun_paths_to_root(sentence);
+        assert_eq!(6, paths.len());
+        assert_eq!("ジョンマッカーシーは -> 作り出した", paths[0]);
+        assert_eq!("AIに関する -> 最初の -> 会議で -> 作り出した", paths[1]);
+        assert_eq!("Xは", replace_noun(&sentence.chunks[0], "X"));
+        assert_eq!("Yという", replace_noun(&sentence.chunks[4], "Y"));
+    }
+
+    #[test]
+    pub fn success_noun_pair_paths() {
+        let sentence = &read_sentences(PARSED.as_bytes()).unwrap()[0];
+        assert_eq!(
+            Some(NounPath::Direct(vec![1, 2, 3])),
+            shortest_path(sentence, 1, 3)
+        );
+        assert_eq!(
+            Some(NounPath::Branched {
+                left: vec![0],
+                right: vec![1, 2, 3],
+                common: 6
+            }),
+            shortest_path(sentence, 0, 1)
+        );
+
+        let paths: Vec<String> = noun_pair_paths(sentence)
+            .iter()
+            .map(|(_, _, path)| path.format(sentence))
+            .collect();
+        // 6つの名詞句から15組
+        assert_eq!(15, paths.len());
+        assert_eq!("Xは | Yに関する -> 最初の -> 会議で | 作り出した", paths[0]);
+        assert_eq!("Xに関する -> Yの", paths[5]);
+        assert_eq!("Xに関する -> 最初の -> Yで", paths[6]);
+        assert_eq!(
+            "Xに関する -> 最初の -> 会議で | Yという -> 用語を | 作り出した",
+            paths[7]
+        );
+        assert_eq!("Xという -> Yを", paths[14]);
+    }
+}
@@ -0,0 +1,271 @@
+use crate::chapter03::answer::Section;
+use crate::chapter05::answer::Sentence;
+use crate::render::{escape_dot, escape_xml, svg_text_width, FONT_FAMILY, FONT_SIZE};
+
+const DOT_FONT: &str = "Noto Sans CJK JP";
+const ARC_COLOR: &str = "#4e79a7";
+const ROOT_COLOR: &str = "#e15759";
+
+const PADDING: f64 = 20.0;
+const LABEL_GAP: f64 = 16.0;
+// 係り元と係り先が1つ離れるごとに弧を高くする
+const ARC_STEP: f64 = 18.0;
+
+// ノードごとに親（係り先）を1つだけ持つ木。係り受け木と節の構造を同じ形で描く
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tree {
+    title: String,
+    labels: Vec<String>,
+    heads: Vec<Option<usize>>,
+}
+
+impl Tree {
+    // 範囲外や自分自身を指す親は根として扱う
+    pub fn new(labels: Vec<String>, heads: Vec<Option<usize>>) -> Tree {
+        let len = labels.len();
+        let heads = (0..len)
+            .map(|i| match heads.get(i) {
+                Some(Some(head)) if *head < len && *head != i => Some(*head),
+                _ => None,
+            })
+            .collect();
+        Tree {
+            title: String::new(),
+            labels,
+            heads,
+        }
+    }
+
+    // ch05-44. 文節を係り先につなぐ
+    pub fn from_sentence(sentence: &Sentence) -> Tree {
+        Tree::new(
+            sentence
+                .chunks
+                .iter()
+                .map(|chunk| chunk.surface())
+                .collect(),
+            sentence.chunks.iter().map(|chunk| chunk.dst).collect(),
+        )
+    }
+
+    // ch03-23 のセクションを、直前にある自分より浅いセクションにつなぐ。
+    // 先頭に記事名のノードを置き、最上位のセクションはそこにつなぐ
+    pub fn from_sections(title: &str, sections: &[Section]) -> Tree {
+        let mut labels = vec![title.to_string()];
+        let mut heads = vec![None];
+        let mut stack: Vec<(u8, usize)> = vec![];
+        for section in sections.iter() {
+            while let Some((level, _)) = stack.last() {
+                if *level < section.level() {
+                    break;
+                }
+                stack.pop();
+            }
+            heads.push(Some(stack.last().map(|(_, i)| *i).unwrap_or(0)));
+            stack.push((section.level(), labels.len()));
+            labels.push(section.section().trim().to_string());
+        }
+        return Tree::new(labels, heads).with_title(title);
+    }
+
+    pub fn with_title(mut self, title: &str) -> Tree {
+        self.title = title.to_string();
+        return self;
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn heads(&self) -> &[Option<usize>] {
+        &self.heads
+    }
+
+    // Graphviz DOT形式。親を上に置き、矢印は子から親へ向ける
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph tree {\n");
+        if !self.title.is_empty() {
+            dot.push_str(&format!(
+                "  label=\"{}\";\n  labelloc=t;\n",
+                escape_dot(&self.title)
+            ));
+        }
+        dot.push_str(&format!(
+            "  graph [fontname=\"{font}\"];\n  node [shape=box, fontname=\"{font}\"];\n",
+            font = DOT_FONT
+        ));
+        for (i, label) in self.labels.iter().enumerate() {
+            dot.push_str(&format!("  n{} [label=\"{}\"];\n", i, escape_dot(label)));
+        }
+        for (i, head) in self.heads.iter().enumerate() {
+            if let Some(head) = head {
+                dot.push_str(&format!("  n{} -> n{} [dir=back];\n", head, i));
+            }
+        }
+        dot.push_str("}\n");
+        return dot;
+    }
+
+    // ラベルを左から並べ、子から親への弧を上に描く
+    pub fn to_svg(&self) -> String {
+        let centers = self.centers();
+        let width = centers
+            .last()
+            .map(|(x, w)| x + w / 2.0 + PADDING)
+            .unwrap_or(PADDING * 2.0);
+        let max_span = self
+            .heads
+            .iter()
+            .enumerate()
+            .filter_map(|(i, head)| head.map(|head| i.abs_diff(head)))
+            .max()
+            .unwrap_or(0);
+        let title_height = if self.title.is_empty() {
+            0.0
+        } else {
+            FONT_SIZE + 16.0
+        };
+        let arc_top = PADDING + title_height;
+        let baseline = arc_top + ARC_STEP * (max_span + 1) as f64 + FONT_SIZE + 8.0;
+        let height = baseline + PADDING;
+        let mut svg = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <svg xmlns=\"http://www.w3.org/2000/svg\" xml:lang=\"ja\" width=\"{w:.1}\" height=\"{h:.1}\" viewBox=\"0 0 {w:.1} {h:.1}\" font-family=\"{font}\" font-size=\"{size}\">\n",
+            w = width,
+            h = height,
+            font = FONT_FAMILY,
+            size = FONT_SIZE
+        );
+        svg.push_str(&format!(
+            "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\"><path d=\"M0 0 L10 5 L0 10 z\" fill=\"{}\"/></marker></defs>\n",
+            ARC_COLOR
+        ));
+        svg.push_str(&format!(
+            "<rect width=\"{:.1}\" height=\"{:.1}\" fill=\"white\"/>\n",
+            width, height
+        ));
+        if !self.title.is_empty() {
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-size=\"{}\">{}</text>\n",
+                width / 2.0,
+                PADDING + FONT_SIZE,
+                FONT_SIZE + 4.0,
+                escape_xml(&self.title)
+            ));
+        }
+        let bottom = baseline - FONT_SIZE - 4.0;
+        for (i, head) in self.heads.iter().enumerate() {
+            let (x, _) = centers[i];
+            match head {
+                Some(head) => {
+                    let (head_x, _) = centers[*head];
+                    let top = bottom - ARC_STEP * i.abs_diff(*head) as f64;
+                    svg.push_str(&format!(
+                        "<path d=\"M{:.1} {:.1} C{:.1} {:.1} {:.1} {:.1} {:.1} {:.1}\" fill=\"none\" stroke=\"{}\" marker-end=\"url(#arrow)\"><title>{} → {}</title></path>\n",
+                        x,
+                        bottom,
+                        x,
+                        top,
+                        head_x,
+                        top,
+                        head_x,
+                        bottom,
+                        ARC_COLOR,
+                        escape_xml(&self.labels[i]),
+                        escape_xml(&self.labels[*head])
+                    ));
+                }
+                None => {
+                    svg.push_str(&format!(
+                        "<line x1=\"{:.1}\" y1=\"{:.1}\" x2=\"{:.1}\" y2=\"{:.1}\" stroke=\"{}\" stroke-dasharray=\"4 2\"/>\n",
+                        x, arc_top, x, bottom, ROOT_COLOR
+                    ));
+                }
+            }
+        }
+        for (i, label) in self.labels.iter().enumerate() {
+            svg.push_str(&format!(
+                "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\"><title>{}</title>{}</text>\n",
+                centers[i].0,
+                baseline,
+                i,
+                escape_xml(label)
+            ));
+        }
+        svg.push_str("</svg>\n");
+        return svg;
+    }
+
+    // ラベルの中心の x 座標と幅
+    fn centers(&self) -> Vec<(f64, f64)> {
+        let mut x = PADDING;
+        let mut centers = vec![];
+        for label in self.labels.iter() {
+            let width = svg_text_width(label).max(FONT_SIZE);
+            centers.push((x + width / 2.0, width));
+            x += width + LABEL_GAP;
+        }
+        return centers;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chapter03::answer::{extract_sections, Article};
+    use crate::chapter05::cabocha::read_sentences;
+    use crate::chapter05::tree::Tree;
+
+    const PARSED: &str = "* 0 2D 0/1 0.0\n\
+                          吾輩\t名詞,代名詞,*,*,*,*,吾輩\n\
+                          は\t助詞,係助詞,*,*,*,*,は\n\
+                          * 1 2D 0/1 0.0\n\
+                          \"猫\"\t名詞,一般,*,*,*,*,\"猫\"\n\
+                          で\t助詞,格助詞,*,*,*,*,で\n\
+                          * 2 -1D 0/1 0.0\n\
+                          ある\t助動詞,*,*,*,*,*,ある\n\
+                          EOS\n";
+
+    #[test]
+    pub fn success_dependency_tree() {
+        let sentence = &read_sentences(PARSED.as_bytes()).unwrap()[0];
+        let tree = Tree::from_sentence(sentence);
+        assert_eq!(vec![Some(2), Some(2), None], tree.heads());
+
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph tree {\n"));
+        assert!(dot.contains("  n1 [label=\"\\\"猫\\\"で\"];\n"));
+        assert!(dot.contains("  n2 -> n0 [dir=back];\n"));
+        assert!(!dot.contains("-> n2"));
+
+        let svg = tree.to_svg();
+        assert!(svg.contains("xml:lang=\"ja\""));
+        assert!(svg.contains("&quot;猫&quot;で</text>"));
+        assert_eq!(2, svg.matches("marker-end").count());
+        assert_eq!(1, svg.matches("stroke-dasharray").count());
+
+        let broken = Tree::new(
+            vec!["a".to_string(), "b".to_string()],
+            vec![Some(0), Some(5)],
+        );
+        assert_eq!(vec![None, None], broken.heads());
+    }
+
+    #[test]
+    pub fn success_section_tree() {
+        let article = Article::new(
+            "イギリス",
+            "本文\n==国名==\n==歴史==\n===中世===\n====王朝====\n===近代===\n==地理==\n",
+        );
+        let tree = Tree::from_sections(article.title(), &extract_sections(&article));
+        assert_eq!(
+            vec!["イギリス", "国名", "歴史", "中世", "王朝", "近代", "地理"],
+            tree.labels()
+        );
+        assert_eq!(
+            vec![None, Some(0), Some(0), Some(2), Some(3), Some(2), Some(0)],
+            tree.heads()
+        );
+        assert!(tree.to_dot().contains("  label=\"イギリス\";\n"));
+        assert!(tree.to_svg().contains(">イギリス</text>"));
+    }
+}
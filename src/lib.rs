@@ -2,3 +2,8 @@ pub mod chapter01;
 pub mod chapter02;
 pub mod chapter03;
 pub mod chapter04;
+pub mod chapter05;
+pub mod render;
+pub mod sentence;
+#[cfg(test)]
+mod test_support;
@@ -0,0 +1,57 @@
+// Graphviz DOT と SVG の出力で共通に使う文字列処理とフォントの設定
+
+// 日本語のグリフを持つフォントを優先し、最後は閲覧環境の sans-serif に任せる
+pub const FONT_FAMILY: &str = "'Hiragino Kaku Gothic ProN', 'Hiragino Sans', 'Noto Sans CJK JP', \
+                               'Noto Sans JP', 'Yu Gothic', 'Meiryo', 'IPAexGothic', sans-serif";
+pub const FONT_SIZE: f64 = 12.0;
+
+pub fn escape_dot(text: &str) -> String {
+    text.replace("\\", "\\\\").replace("\"", "\\\"")
+}
+
+pub fn escape_xml(text: &str) -> String {
+    text.replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(">", "&gt;")
+        .replace("\"", "&quot;")
+        .replace("'", "&apos;")
+}
+
+// 表示幅の目安を全角1文字分を単位として返す。ASCII と半角カナは narrow 文字分とみなす
+pub fn text_width(text: &str, narrow: f64) -> f64 {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii() || ('\u{ff61}'..='\u{ff9f}').contains(&c) {
+                narrow
+            } else {
+                1.0
+            }
+        })
+        .sum()
+}
+
+// SVG に描いたときの幅。プロポーショナルフォントの半角文字は全角の半分より少し広い
+pub fn svg_text_width(text: &str) -> f64 {
+    text_width(text, 0.55) * FONT_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::{escape_dot, escape_xml, svg_text_width, text_width, FONT_SIZE};
+
+    #[test]
+    pub fn success_escape() {
+        assert_eq!("\\\"猫\\\"\\\\", escape_dot("\"猫\"\\"));
+        assert_eq!(
+            "&lt;a href=&quot;x&quot;&gt;&amp;&apos;",
+            escape_xml("<a href=\"x\">&'")
+        );
+    }
+
+    #[test]
+    pub fn success_text_width() {
+        assert_eq!(FONT_SIZE * 2.0, svg_text_width("吾輩"));
+        assert_eq!(FONT_SIZE * 1.1, svg_text_width("AI"));
+        assert_eq!(2.5, text_width("ﾈｺ猫a", 0.5));
+    }
+}
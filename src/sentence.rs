@@ -0,0 +1,169 @@
+// 文末記号
+const TERMINATORS: [char; 6] = ['。', '．', '！', '？', '!', '?'];
+// 文末記号の直後にあれば前の文に含める閉じ括弧
+const CLOSING_BRACKETS: [char; 5] = ['」', '』', '）', ')', '】'];
+const ELLIPSES: [char; 2] = ['…', '‥'];
+// 「……。」と云う、のように引用の後に続けば同じ文とみなす
+const QUOTATION_FOLLOWERS: [char; 3] = ['と', 'っ', '、'];
+
+// 行の中の1文。start は行頭からの文字単位のオフセット
+#[derive(Debug, PartialEq)]
+pub struct SentenceSpan<'a> {
+    pub start: usize,
+    pub text: &'a str,
+}
+
+// 1行（段落）を文に分割する
+// 「」『』の中では分割しない。文末記号で終わる引用の後や、三点リーダの後に空白が続く場合もそこで文を区切る
+pub fn split_sentences(line: &str) -> Vec<SentenceSpan<'_>> {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let mut sentences = vec![];
+    let mut depth: usize = 0;
+    let mut begin = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+        i += 1;
+        match c {
+            '「' | '『' => depth += 1,
+            '」' | '』' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if depth > 0 {
+            continue;
+        }
+        let is_end = if (c == '」' || c == '』') && i >= 2 {
+            TERMINATORS.contains(&chars[i - 2].1)
+                && i < chars.len()
+                && !QUOTATION_FOLLOWERS.contains(&chars[i].1)
+                && !TERMINATORS.contains(&chars[i].1)
+        } else if TERMINATORS.contains(&c) {
+            // 「！？」のように続く文末記号と閉じ括弧はまとめる
+            while i < chars.len()
+                && (TERMINATORS.contains(&chars[i].1) || CLOSING_BRACKETS.contains(&chars[i].1))
+            {
+                i += 1;
+            }
+            true
+        } else if ELLIPSES.contains(&c) || (c == '.' && i >= 3 && is_ascii_ellipsis(&chars, i)) {
+            while i < chars.len() && (ELLIPSES.contains(&chars[i].1) || chars[i].1 == '.') {
+                i += 1;
+            }
+            if i < chars.len() && TERMINATORS.contains(&chars[i].1) {
+                // 「……。」は文末記号の側で区切る
+                false
+            } else {
+                i < chars.len() && chars[i].1.is_whitespace()
+            }
+        } else {
+            false
+        };
+        if is_end {
+            push_sentence(line, &chars, begin, i, &mut sentences);
+            begin = i;
+        }
+    }
+    push_sentence(line, &chars, begin, chars.len(), &mut sentences);
+    return sentences;
+}
+
+fn is_ascii_ellipsis(chars: &[(usize, char)], end: usize) -> bool {
+    chars[end - 3..end].iter().all(|(_, c)| *c == '.')
+}
+
+// chars[begin..end] の前後の空白（全角空白を含む）を除いて追加する
+fn push_sentence<'a>(
+    line: &'a str,
+    chars: &[(usize, char)],
+    mut begin: usize,
+    mut end: usize,
+    sentences: &mut Vec<SentenceSpan<'a>>,
+) {
+    while begin < end && chars[begin].1.is_whitespace() {
+        begin += 1;
+    }
+    while end > begin && chars[end - 1].1.is_whitespace() {
+        end -= 1;
+    }
+    if begin == end {
+        return;
+    }
+    let byte_end = if end < chars.len() {
+        chars[end].0
+    } else {
+        line.len()
+    };
+    sentences.push(SentenceSpan {
+        start: begin,
+        text: &line[chars[begin].0..byte_end],
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sentence::{split_sentences, SentenceSpan};
+
+    fn texts(line: &str) -> Vec<&str> {
+        split_sentences(line).iter().map(|s| s.text).collect()
+    }
+
+    #[test]
+    pub fn success_split_sentences() {
+        assert_eq!(
+            vec![
+                "吾輩は猫である。",
+                "名前はまだ無い。",
+                "どこで生れたかとんと見当がつかぬ！？",
+            ],
+            texts("　吾輩は猫である。名前はまだ無い。どこで生れたかとんと見当がつかぬ！？")
+        );
+        assert_eq!(
+            vec![
+                SentenceSpan {
+                    start: 1,
+                    text: "吾輩は猫である。",
+                },
+                SentenceSpan {
+                    start: 9,
+                    text: "名前はまだ無い",
+                },
+            ],
+            split_sentences("　吾輩は猫である。名前はまだ無い")
+        );
+        assert_eq!(
+            vec![
+                "名前はまだ無い！",
+                "「どこで生れたか？」",
+                "頓と見当がつかぬ"
+            ],
+            texts("名前はまだ無い！「どこで生れたか？」頓と見当がつかぬ")
+        );
+        assert!(split_sentences("").is_empty());
+        assert!(split_sentences("　 ").is_empty());
+    }
+
+    #[test]
+    pub fn success_split_sentences_with_quotations() {
+        assert_eq!(
+            vec![
+                "「おい。どうした。」と主人が云う。",
+                "『猫だ。『猫』だ。』と書いてある。",
+                "（笑い）",
+            ],
+            texts("「おい。どうした。」と主人が云う。『猫だ。『猫』だ。』と書いてある。（笑い）")
+        );
+        assert_eq!(
+            vec!["「どうした。」", "返事はない。"],
+            texts("「どうした。」返事はない。")
+        );
+    }
+
+    #[test]
+    pub fn success_split_sentences_with_ellipses() {
+        assert_eq!(
+            vec!["まあ……", "仕方がない……。", "それで...", "どうする？"],
+            texts("まあ……　仕方がない……。それで... どうする？")
+        );
+        assert_eq!(vec!["それで...どうする？"], texts("それで...どうする？"));
+    }
+}
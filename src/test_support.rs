@@ -0,0 +1,42 @@
+// テスト用の形態素解析結果。1文をトークンの空白区切りで書き、各トークンは
+// 「表層形/原形/品詞/品詞細分類1/活用形」。原形が空なら表層形とし、品詞以降は省略できる
+//   "猫//名詞 が//助詞 鳴い/鳴く/動詞/自立/連用形"
+
+use crate::chapter04::answer::Token;
+
+// 1文を JSON 形式の1行にする
+pub fn sentence_json(text: &str) -> String {
+    let tokens: Vec<serde_json::Value> = text
+        .split_whitespace()
+        .map(|token| {
+            let fields: Vec<&str> = token.split('/').collect();
+            let field = |i: usize| fields.get(i).cloned().unwrap_or("");
+            let base = if field(1).is_empty() {
+                field(0)
+            } else {
+                field(1)
+            };
+            serde_json::json!({
+                "surface": field(0),
+                "base": base,
+                "pos": field(2),
+                "pos1": field(3),
+                "conjugation_form": field(4),
+            })
+        })
+        .collect();
+    return serde_json::to_string(&tokens).unwrap();
+}
+
+// 複数の文を JSON 形式（1行1文）にする
+pub fn sentences_json(sentences: &[&str]) -> String {
+    sentences
+        .iter()
+        .map(|text| format!("{}\n", sentence_json(text)))
+        .collect()
+}
+
+// 1文のトークン列
+pub fn tokens(text: &str) -> Vec<Token> {
+    return serde_json::from_str(&sentence_json(text)).unwrap();
+}